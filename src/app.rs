@@ -1,11 +1,28 @@
-use iced::{ pane_grid, executor, Command, Length, Subscription, Container, Element, Application };
+use iced::{ pane_grid, executor, time, Command, Length, Subscription, Container, Column, Element, Application };
 use iced_native::{ keyboard, Event };
-use std::{ path::PathBuf, fs, collections::HashMap, path, os::unix, env, cell::RefCell, rc::Rc };
+use std::{ path::{ Path, PathBuf }, fs, collections::{ HashMap, HashSet }, path, os::unix, env, cell::RefCell, rc::Rc };
 use crate::style;
+use crate::util::{ self, background, cli::Options, duplicates, exif, file_io, histogram::{ self, Histogram },
+                   keymap::{ self, Keymap, KeyContext }, perceptual_hash, presets::{ self, TagPreset },
+                   session::{ self, Session }, trash };
 use crate::models::*;
 use crate::states::*;
 use crate::views::{ MainView, MenuView };
 
+// images decoded by each step of the background integrity scan
+const INTEGRITY_BATCH: usize = 8;
+// images hashed by each step of finding similar images
+const HASH_BATCH: usize = 8;
+// where the organize process puts corrupt images, as if they were tagged with it
+const CORRUPT_FOLDER: &str = "corrupt";
+const HISTOGRAM_HEIGHT: u32 = 80;
+
+// what to do once every image has been hashed
+enum SimilarityRequest {
+    Cluster,
+    SimilarTo(String),
+}
+
 /*
     TODO: 
     - Consider App struct storing all states vs Views holding states?
@@ -19,15 +36,33 @@ use crate::views::{ MainView, MenuView };
 
 pub struct App {
     pub organize_mode: OrganizeMode,
+    pub organize_filtered: bool,
+    pub organize_corrupt: bool,
+    pub burst_gap: f64,
     pub console_messages: RefCell::<Vec::<String>>,
     pub app_state: AppState,
     pub image_queue: pane_grid::Pane,
     pub pane_state: pane_grid::State<MainView>,
     pub tag_input: Option<pane_grid::Pane>,
+    pub tag_picker: Option<pane_grid::Pane>,
+    pub help: Option<pane_grid::Pane>,
+    pub command_palette: Option<pane_grid::Pane>,
     pub image_display: pane_grid::Pane,
     pub keyboard_state: KeyboardState,
     pub working_directory: Rc::<RefCell::<String>>,
+    pub tags: Rc::<RefCell::<TagRegistry>>,
+    pub session_stats: SessionStats,
+    pub quarantine: Option<PathBuf>,
+    pub history: History,
+    pub keymap: Keymap,
+    pub pending_keys: PendingKeys,
+    pub quit_prompt: bool,
 
+    saved_snapshot: String,
+    unchecked_images: Vec::<String>,
+    unhashed_images: Vec::<String>,
+    hashing: bool,
+    similarity_request: Option<SimilarityRequest>,
     side_panel: pane_grid::Pane,
 }
 
@@ -49,19 +84,49 @@ impl App {
     }
 
     pub fn run_organize_process(self: &mut Self) -> Result<(), std::io::Error> {
-        let side_panel = self.get_state(self.side_panel).side_panel();
+        let tags = Rc::clone(&self.tags);
+        let tags = tags.borrow();
         let image_queue = self.get_state(self.image_queue).image_queue();
 
+        // with the option on, only images the filter selects are organized or trashed
+        let filter = image_queue.filter.as_ref().filter(|_| self.organize_filtered);
+        let images: Vec::<&ImageInfo> = image_queue.image_infos
+                                                   .iter()
+                                                   .filter(|x| filter.map(|f| f.selects(x, &tags)).unwrap_or(true))
+                                                   .collect();
+        if let Some(filter) = filter {
+            self.log(format!("Organizing {} images matching {}", images.len(), filter.text));
+        }
+
+        // companions go along with the image they belong to
+        let marked: Vec::<String> = images.iter()
+                                          .filter(|x| x.marked_for_deletion)
+                                          .flat_map(|x| x.files())
+                                          .collect();
+
+        // nested tags become nested folders, and an image only goes into the most
+        // specific folders it is tagged with (cats rather than animals and animals/cats)
         let mut store = HashMap::<String, Vec::<String>>::new();
-        store = image_queue.image_infos.iter().fold(store, |mut acc, image_info| {
-            for (tag, _) in image_info.tags.iter() {
-                if let Some(tag_label) = side_panel.tags.borrow().get(&tag.to_string()) {
-                    if !acc.contains_key(tag_label) {
-                        acc.insert(tag_label.to_string(), Vec::<String>::new());
+        store = images.iter().filter(|x| !x.marked_for_deletion).fold(store, |mut acc, image_info| {
+            // corrupt images only go to their own folder, whatever they were tagged with
+            if image_info.corrupt.is_some() && self.organize_corrupt {
+                acc.entry(CORRUPT_FOLDER.to_string()).or_insert(Vec::<String>::new()).extend(image_info.files());
+                return acc;
+            }
+
+            for tag in image_info.tags.iter() {
+                if image_info.tags.iter().any(|x| tags.is_descendant_of(*x, *tag)) {
+                    continue;
+                }
+
+                if tags.get(*tag).is_some() {
+                    let tag_path = tags.path(*tag);
+                    if !acc.contains_key(&tag_path) {
+                        acc.insert(tag_path.to_string(), Vec::<String>::new());
                     }
 
-                    if let Some(tag_store) = acc.get_mut(tag_label) {
-                        tag_store.push(image_info.path.clone());
+                    if let Some(tag_store) = acc.get_mut(&tag_path) {
+                        tag_store.extend(image_info.files());
                     }
                 }
             }
@@ -107,7 +172,27 @@ impl App {
                 }
             }
         }
-        
+
+        let mut trashed = HashSet::<String>::new();
+        for file in marked.iter() {
+            let result = match &self.quarantine {
+                Some(quarantine) => trash::move_to_quarantine(Path::new(file), quarantine),
+                None => trash::move_to_trash(Path::new(file))
+            };
+            match result {
+                Ok(destination) => {
+                    self.log(format!("{} moved to {}", file, destination.display()));
+                    trashed.insert(file.clone());
+                }
+                Err(e) => self.log(format!("Error deleting {}: {}", file, e))
+            }
+        }
+        self.get_mut_state(self.image_queue)
+            .image_queue_mut()
+            .remove_paths(&trashed);
+        // the history refers to images by index, which just shifted
+        self.history.clear();
+
         Ok(())
     }
 
@@ -126,73 +211,1072 @@ impl App {
     }
 
     fn load_current_image(self: &mut Self) {
-        if let Some((current_path, tags)) = self.get_current_image_info() {
-            let tags = tags.iter().map(|tag| *tag.clone()).collect();
+        self.get_mut_state(self.image_queue).image_queue_mut().visit_current();
+        let current_image_info = self.get_current_image_info();
+        let state = self.get_state(self.image_queue).image_queue();
+        let duplicates: Vec::<String> = state.duplicates_of(state.selected_image_index)
+                                             .iter()
+                                             .map(|x| self.image_path(*x))
+                                             .collect();
+        let corrupt = state.current().and_then(|x| x.corrupt.clone());
+        let cluster = state.cluster_of(state.selected_image_index);
+        let cluster_position = cluster.iter()
+                                      .position(|x| *x == state.selected_image_index)
+                                      .map(|x| (x + 1, cluster.len()));
+        let display_state = self.get_mut_state(self.image_display).image_display_mut();
+        display_state.duplicates = duplicates;
+        display_state.cluster_position = cluster_position;
+        display_state.corrupt = corrupt;
+        let mut error = None;
+        match current_image_info {
+            Some((current_path, tags)) => {
+                if display_state.current_image_path != current_path {
+                    display_state.page = 0;
+                    error = App::show_image(display_state, &current_path);
+                }
+                display_state.current_image_path = current_path;
+                display_state.current_image_tags = Some(tags);
+            }
+            None => {
+                display_state.current_image_path = String::from("");
+                display_state.current_image_tags = None;
+                display_state.image = None;
+                display_state.image_error = None;
+                display_state.page_count = 0;
+                display_state.histogram = None;
+                display_state.histogram_image = None;
+                display_state.pixels = None;
+                display_state.clipping = None;
+            }
+        }
+        if let Some(error) = error {
+            self.log(error);
+        }
+    }
+
+    // decodes the page the display is on, returning the error to log if it can't be
+    fn show_image(display_state: &mut ImageDisplayState, path: &str) -> Option<String> {
+        match util::image::load(path, display_state.page) {
+            Ok(loaded) => {
+                let show_clipping = display_state.show_clipping;
+                display_state.frame = 0;
+                display_state.playing = true;
+                display_state.histogram = loaded.pixels.as_ref().map(Histogram::compute);
+                display_state.histogram_image = display_state.histogram
+                                                             .as_ref()
+                                                             .map(|x| util::image::rgba_handle(x.render(HISTOGRAM_HEIGHT)));
+                display_state.clipping = loaded.pixels
+                                               .as_ref()
+                                               .filter(|_| show_clipping)
+                                               .map(|x| util::image::rgba_handle(histogram::clipping_overlay(x)));
+                display_state.pixels = loaded.pixels;
+                display_state.image = Some(loaded.content);
+                display_state.image_error = None;
+                display_state.page_count = loaded.pages;
+                None
+            }
+            Err(e) => {
+                display_state.image = None;
+                display_state.image_error = Some(e.clone());
+                display_state.histogram = None;
+                display_state.histogram_image = None;
+                display_state.pixels = None;
+                display_state.clipping = None;
+                Some(format!("Error loading {}: {}", path, e))
+            }
+        }
+    }
+
+    // the overlay is only made while it's on, from the pixels already decoded, so an animation keeps its place
+    pub fn toggle_clipping(self: &mut Self) {
+        let display_state = self.get_mut_state(self.image_display).image_display_mut();
+        let show_clipping = !display_state.show_clipping;
+        display_state.show_clipping = show_clipping;
+        display_state.clipping = display_state.pixels
+                                              .as_ref()
+                                              .filter(|_| show_clipping)
+                                              .map(|x| util::image::rgba_handle(histogram::clipping_overlay(x)));
+    }
+
+    pub fn toggle_animation(self: &mut Self) {
+        if !self.get_mut_state(self.image_display).image_display_mut().toggle_playing() {
+            self.log(String::from("The current image isn't animated"));
+        }
+    }
+
+    // stepping a frame pauses the animation so it stays on that frame
+    pub fn step_frame(self: &mut Self, step: isize) {
+        let display_state = self.get_mut_state(self.image_display).image_display_mut();
+        display_state.playing = false;
+        display_state.step_frame(step);
+    }
+
+    // pages through a multi-page TIFF
+    pub fn change_page(self: &mut Self, step: isize) {
+        let display_state = self.get_mut_state(self.image_display).image_display_mut();
+        if display_state.page_count < 2 {
+            return;
+        }
+
+        let last = display_state.page_count as isize - 1;
+        let page = (display_state.page as isize + step).max(0).min(last) as usize;
+        if page != display_state.page {
+            display_state.page = page;
+            let path = display_state.current_image_path.clone();
+            if let Some(error) = App::show_image(display_state, &path) {
+                self.log(error);
+            }
+        }
+    }
+
+    fn refresh_statistics(self: &mut Self) {
+        let statistics = self.session_stats.compute(&self.get_state(self.image_queue).image_queue().image_infos,
+                                                    &self.tags.borrow());
+        self.tags.borrow_mut().set_usage(statistics.tag_counts.clone());
+        self.get_mut_state(self.side_panel).side_panel_mut().statistics = statistics;
+    }
+
+    fn get_current_image_info(self: &Self) -> Option<(String, Vec::<TagId>)> {
+        let image_info = self.get_state(self.image_queue).image_queue().current()?;
+        let mut tags: Vec::<TagId> = image_info.tags.iter().cloned().collect();
+        tags.sort_unstable();
+
+        Some((image_info.path.clone(), tags))
+    }
 
-            let display_state = self.get_mut_state(self.image_display).image_display_mut();
-            display_state.current_image_path = current_path;
-            display_state.current_image_tags = Some(tags);
+    // a collapsed stack is tagged as a whole
+    pub fn toggle_tag_on_current_image(self: &mut Self, tag: TagId) {
+        let state = self.get_state(self.image_queue).image_queue();
+        let index = state.selected_image_index;
+        if state.stack_of(index).filter(|x| !x.expanded).is_some() {
+            let members = state.stack_members(index);
+            self.toggle_tag_on_images(&members, tag);
+        } else if state.current().is_some() {
+            self.toggle_tag(index, tag);
+            self.history.record(Action::ToggleTag { image: index, tag: tag });
         }
     }
 
-    fn get_current_image_info(self: &Self) -> Option<(String, Vec::<&char>)> {
+    // adds the tag to every image when the current image doesn't have it, otherwise removes it from all of them
+    pub fn toggle_tag_on_images(self: &mut Self, images: &[usize], tag: TagId) {
         let state = self.get_state(self.image_queue).image_queue();
+        let has_tag = state.current().map(|x| x.tags.contains(&tag)).unwrap_or(false);
+        let changed: Vec::<usize> = images.iter()
+                                          .cloned()
+                                          .filter(|x| match state.image_infos.get(*x) {
+                                              Some(image_info) => image_info.tags.contains(&tag) == has_tag,
+                                              None => false
+                                          })
+                                          .collect();
+        for index in changed.iter() {
+            self.toggle_tag(*index, tag);
+        }
+        if !changed.is_empty() {
+            self.history.record(Action::ToggleTags { images: changed, tag: tag });
+        }
+    }
 
-        if !state.image_infos.is_empty() {
-            Some((state.image_infos[state.selected_image_index].path.clone(),
-                 state.image_infos[state.selected_image_index].tags.keys().collect()))
+    pub fn toggle_tag_on_cluster(self: &mut Self, tag: TagId) {
+        let state = self.get_state(self.image_queue).image_queue();
+        let index = state.selected_image_index;
+        let cluster = state.cluster_of(index);
+        if cluster.is_empty() {
+            self.log(format!("{} isn't in a cluster of similar images", self.image_path(index)));
         } else {
-            None
+            self.toggle_tag_on_images(&cluster, tag);
+        }
+    }
+
+    fn toggle_tag(self: &mut Self, index: usize, tag: TagId) {
+        let state = self.get_mut_state(self.image_queue).image_queue_mut();
+        if let Some(image_info) = state.image_infos.get_mut(index) {
+            if !image_info.tags.remove(&tag) {
+                image_info.tags.insert(tag);
+            }
         }
     }
 
-    pub fn toggle_tag_on_current_image(self: &mut Self, key: &char) {
+    // creates every missing tag along the path as one undoable action
+    pub fn create_tag_path(self: &mut Self, path: &str, key: Option<char>) -> Option<TagId> {
+        let existing: HashSet<TagId> = self.tags.borrow().iter().map(|x| x.id).collect();
+        let tag = self.tags.borrow_mut().create_path(path, key);
+        let created: Vec::<Tag> = self.tags.borrow()
+                                           .iter()
+                                           .filter(|x| !existing.contains(&x.id))
+                                           .cloned()
+                                           .collect();
+        if !created.is_empty() {
+            self.history.record(Action::CreateTags { tags: created, children: Vec::<(TagId, TagId)>::new() });
+        }
+
+        tag
+    }
+
+    // tagged with the tag itself or one of its children
+    pub fn jump_to_tag(self: &mut Self, tag: TagId, count: usize, forward: bool) {
+        let tags = Rc::clone(&self.tags);
+        let registry = tags.borrow();
         let state = self.get_mut_state(self.image_queue).image_queue_mut();
-        if state.image_infos[state.selected_image_index].tags.contains_key(key) {
-            state.image_infos[state.selected_image_index].tags.remove(key);
+        let found = if forward {
+            state.select_next_nth(count, |x| registry.implies(&x.tags, tag))
         } else {
-            state.image_infos[state.selected_image_index].tags.insert(key.clone(), ());
+            state.select_prev_nth(count, |x| registry.implies(&x.tags, tag))
+        };
+
+        if !found {
+            self.log(format!("No {} image tagged {}", if forward { "later" } else { "earlier" }, registry.path(tag)));
         }
     }
 
-    pub fn does_tag_exist(self: &Self, key: &String) -> bool {
+    pub fn tag_for_key(self: &Self, key: char) -> Option<TagId> {
+        self.tags.borrow().by_key(key).map(|x| x.id)
+    }
+
+    pub fn open_tag_input(self: &mut Self, target: TagInputTarget) {
+        let initial_value = match target {
+            TagInputTarget::Rename(tag) => self.tags.borrow().name(tag),
+            TagInputTarget::Create(_) => String::from(""),
+            TagInputTarget::Filter => self.get_state(self.image_queue)
+                                          .image_queue()
+                                          .filter
+                                          .as_ref()
+                                          .map(|x| x.text.clone())
+                                          .unwrap_or_default()
+        };
+
+        self.keyboard_state = KeyboardState::None;
+        let tag_input_content = MainView::new(AppView::TagInput(TagInputState {
+            tag_input_value: RefCell::new(initial_value),
+            target: target
+        }));
+
+        let (pane, split) = self.pane_state
+                                .split(pane_grid::Axis::Horizontal, &self.image_display, tag_input_content)
+                                .expect("Pane couldn't split");
+        self.tag_input = Some(pane);
+        self.pane_state.resize(&split, 0.9);
+    }
+
+    pub fn selected_side_panel_tag(self: &Self) -> Option<TagId> {
         self.get_state(self.side_panel)
             .side_panel()
-            .tags
-            .borrow()
-            .contains_key(key)
+            .selected_tag_index
+            .and_then(|x| self.tags.borrow().id_at(x))
+    }
+
+    pub fn focus_side_panel(self: &mut Self, is_focused: bool) {
+        let has_tags = !self.tags.borrow().is_empty();
+        let state = self.get_mut_state(self.side_panel).side_panel_mut();
+        state.is_rebinding = false;
+        if is_focused && has_tags {
+            state.selected_tag_index = Some(0);
+            self.keyboard_state = KeyboardState::SidePanel;
+        } else {
+            state.selected_tag_index = None;
+            self.keyboard_state = KeyboardState::Tagging;
+        }
+    }
+
+    pub fn move_side_panel_selection(self: &mut Self, offset: isize) {
+        let tag_count = self.tags.borrow().len() as isize;
+        let state = self.get_mut_state(self.side_panel).side_panel_mut();
+        if let Some(index) = state.selected_tag_index {
+            let index = index as isize + offset;
+            if index >= 0 && index < tag_count {
+                state.selected_tag_index = Some(index as usize);
+            }
+        }
+    }
+
+    fn image_path(self: &Self, index: usize) -> String {
+        self.get_state(self.image_queue)
+            .image_queue()
+            .image_infos
+            .get(index)
+            .map(|x| x.path.clone())
+            .unwrap_or_default()
+    }
+
+    pub fn mark_current_for_deletion(self: &mut Self) {
+        let marked = self.get_mut_state(self.image_queue).image_queue_mut().mark_current_for_deletion();
+        if let Some(index) = marked {
+            self.history.record(Action::SetDeletion { image: index, marked: true });
+            self.log(format!("Marked {} for deletion", self.image_path(index)));
+        }
+    }
+
+    pub fn restore_last_deleted(self: &mut Self) {
+        let restored = self.get_mut_state(self.image_queue).image_queue_mut().restore_last_deleted();
+        if let Some(index) = restored {
+            self.history.record(Action::SetDeletion { image: index, marked: false });
+            self.log(format!("Restored {}", self.image_path(index)));
+        }
+    }
+
+    // Decodes every image in the background a few at a time, each batch's results
+    // starting the next one, so large folders don't hold up tagging.
+    fn start_integrity_scan(self: &mut Self) -> Command<Message> {
+        self.unchecked_images = self.get_state(self.image_queue)
+                                    .image_queue()
+                                    .image_infos
+                                    .iter()
+                                    .map(|x| x.path.clone())
+                                    .collect();
+        if !self.unchecked_images.is_empty() {
+            self.log(format!("Checking {} images for corruption in the background", self.unchecked_images.len()));
+        }
+        self.next_integrity_batch()
+    }
+
+    fn next_integrity_batch(self: &mut Self) -> Command<Message> {
+        if self.unchecked_images.is_empty() {
+            return Command::none();
+        }
+
+        let rest = self.unchecked_images.split_off(INTEGRITY_BATCH.min(self.unchecked_images.len()));
+        let batch = std::mem::replace(&mut self.unchecked_images, rest);
+        Command::perform(verify_images(batch), Message::IntegrityChecked)
+    }
+
+    fn apply_integrity_results(self: &mut Self, results: Vec::<(String, Option<String>)>) -> Command<Message> {
+        for (path, error) in results {
+            if let Some(error) = &error {
+                self.log(format!("{} is corrupt: {}", path, error));
+            }
+            self.get_mut_state(self.image_queue).image_queue_mut().set_corrupt(&path, error);
+        }
+
+        if self.unchecked_images.is_empty() {
+            let corrupt = self.get_state(self.image_queue)
+                              .image_queue()
+                              .image_infos
+                              .iter()
+                              .filter(|x| x.corrupt.is_some())
+                              .count();
+            match corrupt {
+                0 => self.log(String::from("Integrity check finished, no corrupt images found")),
+                count => self.log(format!("Integrity check finished, {} corrupt images found", count))
+            }
+        }
+        self.next_integrity_batch()
+    }
+
+    pub fn find_duplicates(self: &mut Self) {
+        let paths: Vec::<String> = self.get_state(self.image_queue)
+                                       .image_queue()
+                                       .image_infos
+                                       .iter()
+                                       .map(|x| x.path.clone())
+                                       .collect();
+        let (groups, problems) = duplicates::find_duplicates(&paths);
+        for problem in problems {
+            self.log(problem);
+        }
+
+        let copies: usize = groups.iter().map(|x| x.len() - 1).sum();
+        match groups.len() {
+            0 => self.log(String::from("No identical images found")),
+            count => self.log(format!("Found {} groups of identical images with {} extra copies", count, copies))
+        }
+        self.get_mut_state(self.image_queue).image_queue_mut().set_duplicates(&groups);
+    }
+
+    // Hashes are kept on the images, so only ones not seen before get decoded. That happens
+    // in the background a batch at a time, and the request is answered once they're done.
+    fn request_similarity(self: &mut Self, request: SimilarityRequest) {
+        self.similarity_request = Some(request);
+        if self.hashing {
+            return;
+        }
+
+        self.unhashed_images = self.get_state(self.image_queue)
+                                   .image_queue()
+                                   .image_infos
+                                   .iter()
+                                   .filter(|x| x.hashes.is_none())
+                                   .map(|x| x.path.clone())
+                                   .collect();
+        if !self.unhashed_images.is_empty() {
+            self.log(format!("Hashing {} images in the background", self.unhashed_images.len()));
+        }
+    }
+
+    fn next_hash_batch(self: &mut Self) -> Command<Message> {
+        if self.hashing || self.similarity_request.is_none() {
+            return Command::none();
+        }
+        if self.unhashed_images.is_empty() {
+            self.answer_similarity_request();
+            return Command::none();
+        }
+
+        let rest = self.unhashed_images.split_off(HASH_BATCH.min(self.unhashed_images.len()));
+        let batch = std::mem::replace(&mut self.unhashed_images, rest);
+        self.hashing = true;
+        Command::perform(hash_images(batch), Message::ImagesHashed)
+    }
+
+    fn apply_hashes(self: &mut Self, results: Vec::<(String, Result<perceptual_hash::ImageHashes, String>)>) {
+        self.hashing = false;
+        let state = self.get_mut_state(self.image_queue).image_queue_mut();
+        let mut problems = Vec::<String>::new();
+        for (path, hashes) in results {
+            match (hashes, state.image_infos.iter_mut().find(|x| x.path == path)) {
+                (Ok(hashes), Some(image_info)) => image_info.hashes = Some(hashes),
+                (Err(e), _) => problems.push(format!("Error hashing {}: {}", path, e)),
+                _ => ()
+            }
+        }
+
+        for problem in problems {
+            self.log(problem);
+        }
+    }
+
+    fn answer_similarity_request(self: &mut Self) {
+        match self.similarity_request.take() {
+            Some(SimilarityRequest::Cluster) => self.cluster_hashed_images(),
+            Some(SimilarityRequest::SimilarTo(path)) => self.find_hashed_similar_to(&path),
+            None => ()
+        }
+    }
+
+    pub fn cluster_similar_images(self: &mut Self) {
+        self.request_similarity(SimilarityRequest::Cluster);
+    }
+
+    pub fn find_similar_to_current(self: &mut Self) {
+        if let Some(image_info) = self.get_state(self.image_queue).image_queue().current() {
+            let path = image_info.path.clone();
+            self.request_similarity(SimilarityRequest::SimilarTo(path));
+        }
+    }
+
+    fn cluster_hashed_images(self: &mut Self) {
+        let hashes: Vec::<Option<perceptual_hash::ImageHashes>> = self.get_state(self.image_queue)
+                                                                      .image_queue()
+                                                                      .image_infos
+                                                                      .iter()
+                                                                      .map(|x| x.hashes)
+                                                                      .collect();
+        let clusters = perceptual_hash::cluster(&hashes);
+        let images: usize = clusters.iter().map(|x| x.len()).sum();
+        match clusters.len() {
+            0 => self.log(String::from("No similar images found")),
+            count => self.log(format!("Found {} clusters of similar images covering {} images", count, images))
+        }
+        self.get_mut_state(self.image_queue).image_queue_mut().set_clusters(&clusters);
+    }
+
+    fn find_hashed_similar_to(self: &mut Self, path: &str) {
+        let state = self.get_state(self.image_queue).image_queue();
+        let (index, current) = match state.image_infos.iter().position(|x| x.path == path) {
+            Some(index) => match state.image_infos[index].hashes {
+                Some(current) => (index, current),
+                None => return
+            },
+            None => return
+        };
+
+        let cluster: Vec::<usize> = state.image_infos
+                                         .iter()
+                                         .enumerate()
+                                         .filter(|(i, x)| *i == index || x.hashes.map(|x| x.is_similar(&current)).unwrap_or(false))
+                                         .map(|(i, _)| i)
+                                         .collect();
+        if cluster.len() > 1 {
+            self.log(format!("Found {} images similar to {}", cluster.len() - 1, path));
+            self.get_mut_state(self.image_queue).image_queue_mut().add_cluster(&cluster);
+        } else {
+            self.log(format!("No images similar to {}", path));
+        }
+    }
+
+    // capture times come from EXIF where there is one, otherwise from when the file was written
+    pub fn stack_bursts(self: &mut Self) {
+        let times: Vec::<Option<f64>> = self.get_state(self.image_queue)
+                                            .image_queue()
+                                            .image_infos
+                                            .iter()
+                                            .map(|x| exif::capture_time(&x.path).or_else(|| file_io::modified_time(&x.path)))
+                                            .collect();
+        let bursts = Stack::find_bursts(&times, self.burst_gap);
+        let images: usize = bursts.iter().map(|x| x.len()).sum();
+        match bursts.len() {
+            0 => self.log(format!("No bursts of shots within {} seconds of each other", self.burst_gap)),
+            count => self.log(format!("Stacked {} bursts covering {} images", count, images))
+        }
+        self.get_mut_state(self.image_queue).image_queue_mut().set_stacks(&bursts);
+    }
+
+    pub fn toggle_stack(self: &mut Self) {
+        if self.get_mut_state(self.image_queue).image_queue_mut().toggle_stack().is_none() {
+            self.log(String::from("The current image isn't in a stack"));
+        }
+    }
+
+    pub fn set_stack_pick(self: &mut Self) {
+        match self.get_mut_state(self.image_queue).image_queue_mut().set_pick() {
+            Some(path) => self.log(format!("{} now stands in for its stack", path)),
+            None => self.log(String::from("The current image isn't in a stack"))
+        }
+    }
+
+    // marks every other copy of the current image for deletion
+    pub fn keep_current_copy(self: &mut Self) {
+        let state = self.get_state(self.image_queue).image_queue();
+        let keep = state.selected_image_index;
+        if state.current().is_none() {
+            return;
+        }
+
+        let marked: Vec::<usize> = state.duplicates_of(keep)
+                                        .into_iter()
+                                        .filter(|x| !state.image_infos[*x].marked_for_deletion)
+                                        .collect();
+        if marked.is_empty() {
+            self.log(format!("{} has no unmarked identical copies", self.image_path(keep)));
+            return;
+        }
+
+        self.set_copies_marked(keep, &marked, true);
+        self.history.record(Action::KeepOne { keep: keep, marked: marked.clone() });
+        self.log(format!("Kept {}, marked {} identical copies for deletion", self.image_path(keep), marked.len()));
+    }
+
+    fn set_copies_marked(self: &mut Self, keep: usize, marked: &[usize], is_marked: bool) {
+        let state = self.get_mut_state(self.image_queue).image_queue_mut();
+        for index in marked.iter() {
+            state.set_marked(*index, is_marked);
+        }
+        if state.image_infos.get(keep).filter(|x| x.is_visible()).is_some() {
+            state.selected_image_index = keep;
+        }
+    }
+
+    pub fn cycle_tag_order(self: &mut Self) {
+        let mut order = self.tags.borrow().order;
+        order.next();
+        self.change_tag_order(order);
+    }
+
+    pub fn change_tag_order(self: &mut Self, order: TagOrder) {
+        let previous = self.tags.borrow().order;
+        self.set_tag_order(|x| *x = order);
+        self.history.record(Action::ChangeOrder { previous: previous, next: order });
+    }
+
+    fn set_tag_order<F>(self: &mut Self, change: F) where F: Fn (&mut TagOrder) {
+        let selected = self.selected_side_panel_tag();
+        change(&mut self.tags.borrow_mut().order);
+        self.select_side_panel_tag(selected);
+    }
+
+    pub fn move_tag(self: &mut Self, tag: TagId, offset: isize) {
+        let previous_order = self.tags.borrow().order;
+        let moved = self.tags.borrow_mut().move_tag(tag, offset);
+        if moved {
+            self.history.record(Action::MoveTag { tag: tag, offset: offset, previous_order: previous_order });
+        }
+        self.select_side_panel_tag(Some(tag));
+    }
+
+    pub fn undo(self: &mut Self) {
+        match self.history.undo() {
+            Some(action) => self.apply_action(&action, true),
+            None => self.log(String::from("Nothing to undo"))
+        }
+    }
+
+    pub fn redo(self: &mut Self) {
+        match self.history.redo() {
+            Some(action) => self.apply_action(&action, false),
+            None => self.log(String::from("Nothing to redo"))
+        }
+    }
+
+    // undoing replays the action's inverse, redoing replays the action itself
+    fn apply_action(self: &mut Self, action: &Action, is_undo: bool) {
+        match action {
+            Action::ToggleTag { image, tag } => {
+                self.toggle_tag(*image, *tag);
+                let state = self.get_mut_state(self.image_queue).image_queue_mut();
+                if state.image_infos.get(*image).filter(|x| x.is_visible()).is_some() {
+                    state.selected_image_index = *image;
+                }
+            }
+            Action::ToggleTags { images, tag } => {
+                for image in images.iter() {
+                    self.toggle_tag(*image, *tag);
+                }
+            }
+            Action::CreateTags { tags, children } => {
+                if is_undo {
+                    // tags made since beneath the removed ones move up, remember where they were
+                    let ids: HashSet<TagId> = tags.iter().map(|x| x.id).collect();
+                    let moved: Vec::<(TagId, TagId)> = self.tags
+                                                           .borrow()
+                                                           .iter()
+                                                           .filter(|x| !ids.contains(&x.id))
+                                                           .filter_map(|x| x.parent.filter(|y| ids.contains(y)).map(|y| (x.id, y)))
+                                                           .collect();
+                    for tag in tags.iter().rev() {
+                        self.tags.borrow_mut().remove(tag.id);
+                        let state = self.get_mut_state(self.image_queue).image_queue_mut();
+                        for image_info in state.image_infos.iter_mut() {
+                            image_info.tags.remove(&tag.id);
+                        }
+                    }
+                    self.history.amend_undone(Action::CreateTags { tags: tags.clone(), children: moved });
+                    self.clamp_side_panel_selection();
+                } else {
+                    for tag in tags.iter() {
+                        let mut tag = tag.clone();
+                        // the key may have gone to another tag while this one was undone
+                        let taken = tag.key.and_then(|x| self.tags.borrow().by_key(x).map(|y| y.id)).filter(|x| *x != tag.id);
+                        if let (Some(key), Some(other)) = (tag.key, taken) {
+                            self.log(format!("Key {} now belongs to {}, {} was recreated without it",
+                                             key, self.tags.borrow().path(other), tag.name));
+                            tag.key = None;
+                        }
+                        self.tags.borrow_mut().insert(tag);
+                    }
+                    for (child, parent) in children.iter() {
+                        self.tags.borrow_mut().set_parent(*child, Some(*parent));
+                    }
+                }
+            }
+            Action::SetDeletion { image, marked } => {
+                self.get_mut_state(self.image_queue)
+                    .image_queue_mut()
+                    .set_marked(*image, *marked != is_undo);
+            }
+            Action::KeepOne { keep, marked } => self.set_copies_marked(*keep, marked, !is_undo),
+            Action::MoveTag { tag, offset, previous_order } => {
+                if is_undo {
+                    self.tags.borrow_mut().move_tag(*tag, -offset);
+                    self.tags.borrow_mut().order = *previous_order;
+                } else {
+                    self.tags.borrow_mut().move_tag(*tag, *offset);
+                }
+                self.select_side_panel_tag(Some(*tag));
+            }
+            Action::ChangeOrder { previous, next } => {
+                let order = if is_undo { *previous } else { *next };
+                self.set_tag_order(|x| *x = order);
+            }
+        }
+    }
+
+    // keeps the same tag selected when the side panel order changes
+    fn select_side_panel_tag(self: &mut Self, tag: Option<TagId>) {
+        let index = tag.and_then(|x| self.tags.borrow().index_of(x));
+        if index.is_some() {
+            self.get_mut_state(self.side_panel).side_panel_mut().selected_tag_index = index;
+        }
+    }
+
+    pub fn start_rebinding_tag(self: &mut Self, tag: TagId) {
+        self.get_mut_state(self.side_panel).side_panel_mut().is_rebinding = true;
+        self.keyboard_state = KeyboardState::Rebinding(tag);
+    }
+
+    pub fn rebind_tag(self: &mut Self, tag: TagId, key: Option<char>) {
+        let action = key.and_then(|x| self.keymap.reserved_by(x).map(|y| (x, y)));
+        let result = match action {
+            Some((key, action)) => Err(format!("{} is bound to {}", key, action.name())),
+            None => self.tags.borrow_mut().rebind(tag, key)
+        };
+        if let Err(e) = result {
+            self.log(format!("Error rebinding tag: {}", e));
+        }
+
+        self.get_mut_state(self.side_panel).side_panel_mut().is_rebinding = false;
+        self.keyboard_state = KeyboardState::SidePanel;
+    }
+
+    pub fn rename_tag(self: &mut Self, tag: TagId, name: String) {
+        let result = self.tags.borrow_mut().rename(tag, name);
+        if let Err(e) = result {
+            self.log(format!("Error renaming tag: {}", e));
+        }
+    }
+
+    // removes the tag from the registry and strips it from every image
+    pub fn remove_tag(self: &mut Self, tag: TagId) {
+        if let Some(removed) = self.tags.borrow_mut().remove(tag) {
+            self.log(format!("Removed tag {}", removed.name));
+        }
+
+        let state = self.get_mut_state(self.image_queue).image_queue_mut();
+        for image_info in state.image_infos.iter_mut() {
+            image_info.tags.remove(&tag);
+        }
+        self.history.forget_tag(tag);
+        self.clamp_side_panel_selection();
+    }
+
+    fn clamp_side_panel_selection(self: &mut Self) {
+        let tag_count = self.tags.borrow().len();
+        let state = self.get_mut_state(self.side_panel).side_panel_mut();
+        if tag_count == 0 {
+            self.focus_side_panel(false);
+        } else if let Some(index) = state.selected_tag_index {
+            if index >= tag_count {
+                state.selected_tag_index = Some(tag_count - 1);
+            }
+        }
+    }
+
+    pub fn merge_preset(self: &mut Self, path: &Path, source: &str) {
+        match TagPreset::load(path) {
+            Ok(preset) => {
+                let conflicts = preset.merge_into(&mut self.tags.borrow_mut(), source);
+                self.log(format!("Merged {} preset {}", source, path.display()));
+                for conflict in conflicts {
+                    self.log(conflict.to_string());
+                }
+            }
+            Err(e) => self.log(format!("Error loading {} preset {}: {}", source, path.display(), e))
+        }
+    }
+
+    pub fn save_preset(self: &Self, path: &Path) {
+        match TagPreset::from_registry(&self.tags.borrow()).save(path) {
+            Ok(_) => self.log(format!("Saved tags to {}", path.display())),
+            Err(e) => self.log(format!("Error saving tags to {}: {}", path.display(), e))
+        }
+    }
+
+    pub fn merge_global_preset(self: &mut Self) {
+        match presets::global_preset_path() {
+            Some(path) => self.merge_preset(&path, "global"),
+            None => self.log(String::from("Error finding the user config directory"))
+        }
+    }
+
+    pub fn merge_project_preset(self: &mut Self) {
+        self.merge_preset(&presets::project_preset_path(), "project");
+    }
+
+    pub fn save_global_preset(self: &Self) {
+        match presets::global_preset_path() {
+            Some(path) => self.save_preset(&path),
+            None => self.log(String::from("Error finding the user config directory"))
+        }
+    }
+
+    pub fn save_project_preset(self: &Self) {
+        self.save_preset(&presets::project_preset_path());
+    }
+
+    fn capture_session(self: &Self) -> Session {
+        Session::capture(&self.tags.borrow(),
+                         &self.get_state(self.image_queue).image_queue().image_infos,
+                         &self.history)
+    }
+
+    pub fn has_unsaved_changes(self: &Self) -> bool {
+        self.capture_session().snapshot() != self.saved_snapshot
+    }
+
+    pub fn save_session(self: &mut Self) -> bool {
+        let path = session::session_path();
+        let session = self.capture_session();
+        match session.save(&path) {
+            Ok(_) => {
+                self.log(format!("Saved session to {}", path.display()));
+                self.saved_snapshot = session.snapshot();
+                true
+            }
+            Err(e) => {
+                self.log(format!("Error saving session to {}: {}", path.display(), e));
+                false
+            }
+        }
+    }
+
+    // asks about saving first when there are tag changes that quitting would lose
+    pub fn request_quit(self: &mut Self) {
+        if self.has_unsaved_changes() {
+            self.app_state = AppState::Menu;
+            self.quit_prompt = true;
+        } else {
+            self.quit(false);
+        }
+    }
+
+    // stays open if saving fails so nothing is lost
+    pub fn quit(self: &mut Self, save: bool) {
+        self.quit_prompt = false;
+        if save && !self.save_session() {
+            return;
+        }
+
+        std::process::exit(0);
+    }
+
+    pub fn load_session(self: &mut Self) {
+        let path = session::session_path();
+        match Session::load(&path) {
+            Ok(session) => {
+                let tags = Rc::clone(&self.tags);
+                let state = self.get_mut_state(self.image_queue).image_queue_mut();
+                let missing = session.restore(&mut tags.borrow_mut(), &mut state.image_infos);
+                let history = History::from_saved(&session.history, &tags.borrow(), &state.image_infos);
+                state.refresh_filter();
+                self.history = history;
+                self.log(format!("Loaded session from {}", path.display()));
+                if !missing.is_empty() {
+                    self.log(format!("{} tagged images from the session are no longer in the folder", missing.len()));
+                }
+                self.session_stats = SessionStats::new(&self.get_state(self.image_queue).image_queue().image_infos);
+                self.saved_snapshot = self.capture_session().snapshot();
+            }
+            Err(e) => self.log(format!("Error loading session from {}: {}", path.display(), e))
+        }
+    }
+
+    fn load_startup_presets(self: &mut Self, options: &Options) {
+        if options.use_global_preset {
+            self.merge_global_preset();
+        }
+        if options.use_project_preset {
+            self.merge_project_preset();
+        }
+        for path in options.presets.iter() {
+            self.merge_preset(path, &path.display().to_string());
+        }
+        if let Some(path) = &options.save_preset {
+            self.save_preset(path);
+        }
+    }
+
+    fn load_keymap(options: &Options) -> (Keymap, Vec::<String>) {
+        let path = match &options.keymap {
+            Some(path) => Some(path.clone()),
+            None => keymap::keymap_path().filter(|x| x.exists())
+        };
+
+        match path {
+            Some(path) => match Keymap::load(&path) {
+                Ok(loaded) => loaded,
+                Err(e) => (Keymap::new(), vec![format!("Error loading keymap {}: {}", path.display(), e)])
+            },
+            None => (Keymap::new(), Vec::<String>::new())
+        }
+    }
+
+    // tags from presets and sessions may use keys the keymap has since taken
+    fn report_reserved_tag_keys(self: &Self) {
+        for tag in self.tags.borrow().iter() {
+            let action = tag.key.and_then(|x| self.keymap.reserved_by(x).map(|y| (x, y)));
+            if let Some((key, action)) = action {
+                self.log(format!("Key {} of tag {} is bound to {}, apply the tag by searching instead",
+                                 key, tag.name, action.name()));
+            }
+        }
+    }
+
+    pub fn toggle_help(self: &mut Self) {
+        match self.help {
+            Some(help) => {
+                self.pane_state.close(&help);
+                self.help = None;
+            }
+            None => {
+                let help_content = MainView::new(AppView::Help(HelpState::new(&self.keymap)));
+                let (pane, split) = self.pane_state
+                                        .split(pane_grid::Axis::Vertical, &self.image_display, help_content)
+                                        .expect("Pane couldn't split");
+                self.help = Some(pane);
+                self.pane_state.resize(&split, 0.6);
+            }
+        }
+    }
+
+    pub fn open_command_palette(self: &mut Self) {
+        // the palette returns to tagging, so it can't leave the side panel focused
+        self.focus_side_panel(false);
+        self.keyboard_state = KeyboardState::None;
+        let command_palette_content = MainView::new(AppView::CommandPalette(CommandPaletteState::new(&self.keymap)));
+
+        let (pane, split) = self.pane_state
+                                .split(pane_grid::Axis::Horizontal, &self.image_display, command_palette_content)
+                                .expect("Pane couldn't split");
+        self.command_palette = Some(pane);
+        self.pane_state.resize(&split, 0.6);
+    }
+
+    fn find_tag(self: &Self, path: &str) -> Option<TagId> {
+        let tag = self.tags.borrow().find_path(path);
+        if tag.is_none() {
+            self.log(format!("No tag named {}", path));
+        }
+
+        tag
+    }
+
+    // an empty query clears the filter, one matching nothing leaves the old filter in place
+    pub fn apply_filter(self: &mut Self, text: &str) {
+        if text.trim().is_empty() {
+            let state = self.get_mut_state(self.image_queue).image_queue_mut();
+            if state.filter.is_some() {
+                state.set_filter(None);
+                self.log(String::from("Filter cleared"));
+            }
+            return;
+        }
+
+        let filter = Filter::parse(text, &self.tags.borrow());
+        match filter {
+            Ok(filter) => {
+                let state = self.get_mut_state(self.image_queue).image_queue_mut();
+                let previous = state.filter.take();
+                if state.set_filter(Some(filter)) == 0 {
+                    state.set_filter(previous);
+                    self.log(format!("No images match {}", text.trim()));
+                }
+            }
+            Err(e) => self.log(format!("Error in filter {}: {}", text.trim(), e))
+        }
+    }
+
+    // pending keys and the filter, shown beside the progress bar
+    fn status(self: &Self) -> String {
+        let mut status = self.pending_keys.label();
+        let state = self.get_state(self.image_queue).image_queue();
+        if let Some(filter) = &state.filter {
+            let shown = state.image_infos.iter().filter(|x| x.is_visible()).count();
+            status.push_str(&format!("    Filter: {} ({} of {})", filter.text, shown, state.image_infos.len()));
+        }
+
+        status
+    }
+
+    pub fn run_palette_command(self: &mut Self, command: PaletteCommand) {
+        match command {
+            PaletteCommand::Action(action) => match action.context() {
+                KeyContext::Menu => MenuView::perform(self, action),
+                _ => MainView::perform(self, action, None)
+            },
+            PaletteCommand::Jump(number) => {
+                self.get_mut_state(self.image_queue).image_queue_mut().select_number(number);
+            }
+            PaletteCommand::JumpToTag(path) => {
+                if let Some(tag) = self.find_tag(&path) {
+                    self.jump_to_tag(tag, 1, true);
+                }
+            }
+            PaletteCommand::Mode(mode) => self.organize_mode = mode,
+            PaletteCommand::Sort(order) => self.change_tag_order(order),
+            PaletteCommand::Filter(query) => self.apply_filter(&query),
+            PaletteCommand::CreateTag(path) => {
+                if self.create_tag_path(&path, None).is_some() {
+                    self.log(format!("Created tag {}", path));
+                }
+            }
+            PaletteCommand::ApplyTag(path) => {
+                if let Some(tag) = self.find_tag(&path) {
+                    self.toggle_tag_on_current_image(tag);
+                }
+            }
+            PaletteCommand::RenameTag(path, name) => {
+                if let Some(tag) = self.find_tag(&path) {
+                    self.rename_tag(tag, name);
+                }
+            }
+            PaletteCommand::DeleteTag(path) => {
+                if let Some(tag) = self.find_tag(&path) {
+                    self.remove_tag(tag);
+                }
+            }
+        }
+    }
+
+    pub fn open_tag_picker(self: &mut Self) {
+        self.keyboard_state = KeyboardState::None;
+        let tag_picker_content = MainView::new(AppView::TagPicker(TagPickerState {
+            query: RefCell::new(String::from("")),
+            tags: Rc::clone(&self.tags)
+        }));
+
+        let (pane, split) = self.pane_state
+                                .split(pane_grid::Axis::Horizontal, &self.image_display, tag_picker_content)
+                                .expect("Pane couldn't split");
+        self.tag_picker = Some(pane);
+        self.pane_state.resize(&split, 0.6);
+    }
+}
+
+// This version of iced closes the window without telling the application, so there's no
+// asking about unsaved changes. The app is dropped on the way out though, and saves them then.
+impl Drop for App {
+    fn drop(self: &mut Self) {
+        if !self.has_unsaved_changes() {
+            return;
+        }
+
+        let path = session::session_path();
+        match self.capture_session().save(&path) {
+            Ok(_) => println!("Window closed with unsaved changes, saved session to {}", path.display()),
+            Err(e) => println!("Window closed with unsaved changes, error saving session to {}: {}", path.display(), e)
+        }
     }
 }
 
 impl Application for App {
     type Executor = executor::Default;
     type Message = Message;
-    type Flags = ();
+    type Flags = Options;
 
-    fn new(_flags: ()) -> (App, Command<Message>) {
+    fn new(options: Options) -> (App, Command<Message>) {
         let working_directory: String;
-        let args: Vec<String> = env::args().collect();
-        if args.len() == 1 {
-            let error_message = "Error getting current directory";
-            working_directory = String::from(std::env::current_dir().expect(error_message)
-                                                                    .to_str()
-                                                                    .expect(error_message));
-        } else {
-            working_directory = args[1].clone();
+        match &options.working_directory {
+            None => {
+                let error_message = "Error getting current directory";
+                working_directory = String::from(std::env::current_dir().expect(error_message)
+                                                                        .to_str()
+                                                                        .expect(error_message));
+            }
+            Some(directory) => working_directory = directory.clone()
         }
 
         assert!(env::set_current_dir(&PathBuf::from(&working_directory)).is_ok());
         let working_directory = Rc::new(RefCell::new(working_directory));
+        let tags = Rc::new(RefCell::new(TagRegistry::new()));
+        let (keymap, keymap_problems) = App::load_keymap(&options);
 
         let pane_content = MainView::new(AppView::SidePanel(SidePanelState {
             label: String::from("Tags"),
-            tags: RefCell::new(HashMap::<String,String>::new())
+            tags: Rc::clone(&tags),
+            selected_tag_index: None,
+            is_rebinding: false,
+            statistics: TagStatistics::default(),
+            help: keymap.help_lines(KeyContext::SidePanel)
         }));
-        let image_queue_content = MainView::new(AppView::ImageQueue(ImageQueueState::new()));
+        let image_queue = ImageQueueState::new(Rc::clone(&tags));
+        let session_stats = SessionStats::new(&image_queue.image_infos);
+        let image_queue_content = MainView::new(AppView::ImageQueue(image_queue));
         let image_display_content = MainView::new(AppView::ImageDisplay(ImageDisplayState {
             root_path: Rc::clone(&working_directory),
             current_image_path: "".to_string(),
-            current_image_tags: None
+            image: None,
+            image_error: None,
+            page: 0,
+            page_count: 0,
+            frame: 0,
+            playing: true,
+            corrupt: None,
+            histogram: None,
+            histogram_image: None,
+            pixels: None,
+            clipping: None,
+            show_clipping: false,
+            current_image_tags: None,
+            duplicates: Vec::<String>::new(),
+            cluster_position: None,
+            tags: Rc::clone(&tags)
         }));
 
         let (mut state, pane) = pane_grid::State::new(pane_content);
@@ -208,21 +1292,52 @@ impl Application for App {
         state.resize(&image_queue_split, 0.9);
         state.resize(&image_display_split, 0.1);
 
-        (App { 
+        let mut app = App {
             app_state: AppState::Menu,
             pane_state: state,
             side_panel: pane,
             image_queue: image_queue_pane,
             image_display: image_display_pane,
             tag_input: None,
+            tag_picker: None,
+            help: None,
+            command_palette: None,
             keyboard_state: KeyboardState::Tagging,
             organize_mode: OrganizeMode::Copy,
+            organize_filtered: false,
+            organize_corrupt: false,
+            burst_gap: options.burst_gap.unwrap_or(DEFAULT_BURST_GAP),
             console_messages: RefCell::new(Vec::<String>::new()),
-            working_directory: working_directory
-        }, Command::none())
+            working_directory: working_directory,
+            tags: tags,
+            session_stats: session_stats,
+            quarantine: options.quarantine.clone(),
+            history: History::new(),
+            keymap: keymap,
+            pending_keys: PendingKeys::default(),
+            quit_prompt: false,
+            saved_snapshot: String::from(""),
+            unchecked_images: Vec::<String>::new(),
+            unhashed_images: Vec::<String>::new(),
+            hashing: false,
+            similarity_request: None
+        };
+        for problem in keymap_problems {
+            app.log(problem);
+        }
+        if session::session_path().exists() {
+            app.load_session();
+        }
+        app.load_startup_presets(&options);
+        app.report_reserved_tag_keys();
+        app.saved_snapshot = app.capture_session().snapshot();
+        let command = app.start_integrity_scan();
+
+        (app, command)
     }
 
     fn update(&mut self, message: Message) -> Command<Message> {
+        let mut command = Command::none();
         match message {
             Message::EventOccurred(event) => {
                 self.handle_event(event);
@@ -243,24 +1358,105 @@ impl Application for App {
             Message::TextInputSubmitted => {
                 if let Some(tag_input) = self.tag_input {
                     let state = self.get_state(tag_input).tag_input();
-                    self.get_state(self.side_panel)
-                        .side_panel()
-                        .insert(state.tag.to_string(), state.tag_input_value.borrow().clone());
+                    let target = state.target;
+                    let name = state.tag_input_value.borrow().trim().to_string();
                     state.set(String::from(""));
                     self.pane_state.close(&tag_input);
+
+                    self.keyboard_state = KeyboardState::Tagging;
+                    if let TagInputTarget::Filter = target {
+                        self.apply_filter(&name);
+                    } else if !name.is_empty() {
+                        match target {
+                            TagInputTarget::Create(key) => {
+                                let tag = self.create_tag_path(&name, Some(key));
+                                if let Some(tag) = tag {
+                                    self.toggle_tag_on_current_image(tag);
+                                }
+                            }
+                            TagInputTarget::Rename(tag) => {
+                                self.rename_tag(tag, name);
+                                self.keyboard_state = KeyboardState::SidePanel;
+                            }
+                            _ => ()
+                        }
+                    } else if let TagInputTarget::Rename(_) = target {
+                        self.keyboard_state = KeyboardState::SidePanel;
+                    }
                 }
 
-                self.keyboard_state = KeyboardState::Tagging;
                 self.tag_input = None;
             }
+            Message::TagPickerChanged(text) => {
+                if let Some(tag_picker) = self.tag_picker {
+                    self.get_state(tag_picker)
+                        .tag_picker()
+                        .set(text);
+                }
+            }
+            Message::TagPickerSubmitted => {
+                if let Some(tag_picker) = self.tag_picker {
+                    let state = self.get_state(tag_picker).tag_picker();
+                    let query = state.query.borrow().trim().to_string();
+                    let tag = match state.best_match() {
+                        Some(tag) => Some(tag),
+                        None => self.create_tag_path(&query, None)
+                    };
+
+                    if let Some(tag) = tag {
+                        self.toggle_tag_on_current_image(tag);
+                    }
+                    self.pane_state.close(&tag_picker);
+                }
+
+                self.keyboard_state = KeyboardState::Tagging;
+                self.tag_picker = None;
+            }
+            Message::CommandPaletteChanged(text) => {
+                if let Some(command_palette) = self.command_palette {
+                    self.get_state(command_palette)
+                        .command_palette()
+                        .set(text);
+                }
+            }
+            Message::CommandPaletteSubmitted => {
+                if let Some(command_palette) = self.command_palette {
+                    let state = self.get_state(command_palette).command_palette();
+                    let is_empty = state.query.borrow().trim().is_empty();
+                    let command = state.command();
+                    self.pane_state.close(&command_palette);
+                    self.command_palette = None;
+                    self.keyboard_state = KeyboardState::Tagging;
+
+                    match command {
+                        Ok(command) if !is_empty => self.run_palette_command(command),
+                        Err(e) if !is_empty => self.log(e),
+                        _ => ()
+                    }
+                }
+            }
             Message::SelectedOrganizeMode(mode) => {
                 self.organize_mode = mode;
             }
+            Message::IntegrityChecked(results) => {
+                command = self.apply_integrity_results(results);
+            }
+            Message::ImagesHashed(results) => {
+                self.apply_hashes(results);
+            }
+            Message::AnimationTick => {
+                // only the frame changes, so there's nothing else to refresh
+                self.get_mut_state(self.image_display).image_display_mut().step_frame(1);
+                return Command::none();
+            }
         }
 
+        // a similarity request from a key or the menu starts hashing, each batch's results start the next
+        let hashing = self.next_hash_batch();
         self.load_current_image();
+        self.refresh_statistics();
 
-        Command::none()
+        Command::batch(vec![command, hashing])
     }
 
     fn title(&self) -> String {
@@ -268,7 +1464,11 @@ impl Application for App {
     }
 
     fn subscription(&self) -> Subscription<Message> {
-        iced_native::subscription::events().map(Message::EventOccurred)
+        let events = iced_native::subscription::events().map(Message::EventOccurred);
+        match self.get_state(self.image_display).image_display().frame_delay() {
+            Some(delay) => Subscription::batch(vec![events, time::every(delay).map(|_| Message::AnimationTick)]),
+            None => events
+        }
     }
 
     fn view(&mut self) -> Element<Message> {
@@ -281,7 +1481,13 @@ impl Application for App {
                     .into()
             }
             AppState::Tagging => {
-                Container::new(MainView::view(&mut self.pane_state))
+                let progress = MainView::progress_view(&self.get_state(self.side_panel).side_panel().statistics,
+                                                       self.status());
+                let column = Column::<'_, Message>::new()
+                                 .push(progress)
+                                 .push(MainView::view(&mut self.pane_state));
+
+                Container::new(column)
                     .width(Length::Fill)
                     .height(Length::Fill)
                     .padding(5)
@@ -291,3 +1497,38 @@ impl Application for App {
         }
     }
 }
+
+async fn verify_images(paths: Vec::<String>) -> Vec::<(String, Option<String>)> {
+    let batch = paths.clone();
+    let verified = background::run(move || {
+        paths.into_iter()
+             .map(|path| {
+                 let error = background::catch_panic(|| util::image::verify(&path)).err();
+                 (path, error)
+             })
+             .collect()
+    });
+
+    // the images are still reported so the scan carries on with the next batch
+    match verified.await {
+        Ok(results) => results,
+        Err(e) => batch.into_iter().map(|path| (path, Some(format!("couldn't be checked: {}", e)))).collect()
+    }
+}
+
+async fn hash_images(paths: Vec::<String>) -> Vec::<(String, Result<perceptual_hash::ImageHashes, String>)> {
+    let batch = paths.clone();
+    let hashed = background::run(move || {
+        paths.into_iter()
+             .map(|path| {
+                 let hashes = background::catch_panic(|| perceptual_hash::compute(&path));
+                 (path, hashes)
+             })
+             .collect()
+    });
+
+    match hashed.await {
+        Ok(results) => results,
+        Err(e) => batch.into_iter().map(|path| (path, Err(e.clone()))).collect()
+    }
+}
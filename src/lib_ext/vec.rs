@@ -1,6 +1,11 @@
 pub trait GetWhere<T> { 
     fn next<F>(self: &Self, index: usize, predicate: F) -> Option<usize> where F: Fn (&T) -> bool;
     fn prev<F>(self: &Self, index: usize, predicate: F) -> Option<usize> where F: Fn (&T) -> bool;
+    fn next_nth<F>(self: &Self, index: usize, count: usize, predicate: F) -> Option<usize> where F: Fn (&T) -> bool;
+    fn prev_nth<F>(self: &Self, index: usize, count: usize, predicate: F) -> Option<usize> where F: Fn (&T) -> bool;
+    fn first_where<F>(self: &Self, predicate: F) -> Option<usize> where F: Fn (&T) -> bool;
+    fn last_where<F>(self: &Self, predicate: F) -> Option<usize> where F: Fn (&T) -> bool;
+    fn nth_where<F>(self: &Self, n: usize, predicate: F) -> Option<usize> where F: Fn (&T) -> bool;
 }
 
 impl <T> GetWhere<T> for std::vec::Vec<T> {
@@ -31,4 +36,52 @@ impl <T> GetWhere<T> for std::vec::Vec<T> {
         } 
         result
     }
+
+    // skips count matches ahead, stopping at the last match when there are fewer
+    fn next_nth<F>(self: &Self, i: usize, count: usize, predicate: F) -> Option<usize> where F: Fn (&T) -> bool {
+        let mut result = None;
+        let mut current = i;
+        for _ in 0..count {
+            match self.next(current, &predicate) {
+                Some(index) => {
+                    result = Some(index);
+                    current = index;
+                }
+                None => break
+            }
+        }
+        result
+    }
+
+    fn prev_nth<F>(self: &Self, i: usize, count: usize, predicate: F) -> Option<usize> where F: Fn (&T) -> bool {
+        let mut result = None;
+        let mut current = i;
+        for _ in 0..count {
+            match self.prev(current, &predicate) {
+                Some(index) => {
+                    result = Some(index);
+                    current = index;
+                }
+                None => break
+            }
+        }
+        result
+    }
+
+    fn first_where<F>(self: &Self, predicate: F) -> Option<usize> where F: Fn (&T) -> bool {
+        self.iter().position(|x| predicate(x))
+    }
+
+    fn last_where<F>(self: &Self, predicate: F) -> Option<usize> where F: Fn (&T) -> bool {
+        self.iter().rposition(|x| predicate(x))
+    }
+
+    // zero based, like Iterator::nth
+    fn nth_where<F>(self: &Self, n: usize, predicate: F) -> Option<usize> where F: Fn (&T) -> bool {
+        self.iter()
+            .enumerate()
+            .filter(|(_, x)| predicate(x))
+            .nth(n)
+            .map(|(index, _)| index)
+    }
 }
@@ -1,5 +1,6 @@
 use std::env;
 use iced::{ Settings, Application };
+use util::cli::Options;
 
 mod app;
 mod style;
@@ -24,5 +25,21 @@ fn main() {
         }
     }
 
-    app::App::run(Settings::default()); 
+    let args: Vec<String> = env::args().collect();
+    let mut options = match Options::parse(&args) {
+        Ok(options) => options,
+        Err(e) => {
+            println!("{}", e);
+            return;
+        }
+    };
+
+    // preset paths are relative to where we were launched, not the folder being organized
+    let current_dir = env::current_dir().expect("Error getting current directory");
+    options.presets = options.presets.iter().map(|x| current_dir.join(x)).collect();
+    options.save_preset = options.save_preset.map(|x| current_dir.join(x));
+    options.quarantine = options.quarantine.map(|x| current_dir.join(x));
+    options.keymap = options.keymap.map(|x| current_dir.join(x));
+
+    app::App::run(Settings::with_flags(options));
 }
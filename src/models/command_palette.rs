@@ -0,0 +1,200 @@
+use iced::{ Scrollable, scrollable, Length, Column, Row, Container, Element, Align, Text };
+use iced_native::{ text_input, TextInput };
+use std::{ cmp::Reverse, cell::RefCell };
+
+use crate::states::{ Message, OrganizeMode };
+use crate::models::TagOrder;
+use crate::util::{ fuzzy, keymap::{ Keymap, KeyAction, KeyContext } };
+use crate::style;
+
+const MAX_RESULTS: usize = 10;
+
+#[derive(Debug, Clone)]
+pub enum PaletteCommand {
+    Action(KeyAction),
+    Jump(usize),
+    JumpToTag(String),
+    Mode(OrganizeMode),
+    Sort(TagOrder),
+    Filter(String),
+    CreateTag(String),
+    ApplyTag(String),
+    RenameTag(String, String),
+    DeleteTag(String),
+}
+
+#[derive(Debug, Clone, Copy)]
+enum CommandKind {
+    Action(KeyAction),
+    Jump,
+    JumpToTag,
+    Mode,
+    Sort,
+    Filter,
+    CreateTag,
+    ApplyTag,
+    RenameTag,
+    DeleteTag,
+}
+
+// (name, argument, description, kind)
+const ARGUMENT_COMMANDS: [(&str, &str, &str, CommandKind); 9] = [
+    ("jump", "<number>", "Go to image N", CommandKind::Jump),
+    ("jump tag", "<tag>", "Next image with a tag", CommandKind::JumpToTag),
+    ("mode", "copy|move|link", "Set the organize mode", CommandKind::Mode),
+    ("sort", "manual|key|label|usage", "Set the side panel tag order", CommandKind::Sort),
+    ("filter", "<query>", "Only show matching images, like cats AND NOT outdoor", CommandKind::Filter),
+    ("tag create", "<tag>", "Create a tag, a/b creates b beneath a", CommandKind::CreateTag),
+    ("tag apply", "<tag>", "Toggle a tag on the current image", CommandKind::ApplyTag),
+    ("tag rename", "<tag> to <new name>", "Rename a tag, a path moves it", CommandKind::RenameTag),
+    ("tag delete", "<tag>", "Delete a tag from every image", CommandKind::DeleteTag),
+];
+
+#[derive(Debug)]
+pub struct PaletteEntry {
+    pub name: String,
+    pub argument: Option<&'static str>,
+    pub description: String,
+    kind: CommandKind,
+}
+
+#[derive(Debug)]
+pub struct CommandPaletteState {
+    pub query: RefCell<String>,
+    pub entries: Vec::<PaletteEntry>,
+}
+
+impl CommandPaletteState {
+    pub fn new(keymap: &Keymap) -> CommandPaletteState {
+        // side panel actions work on the selected tag and tag jumps wait for a key,
+        // the tag commands below cover both
+        let actions = KeyAction::ALL.iter()
+                                    .filter(|x| x.context() != KeyContext::SidePanel)
+                                    .filter(|x| match x {
+                                        KeyAction::NextWithTag | KeyAction::PrevWithTag |
+                                        KeyAction::CloseMenu | KeyAction::CommandPalette => false,
+                                        _ => true
+                                    })
+                                    .map(|x| PaletteEntry {
+                                        name: x.name().replace('_', " "),
+                                        argument: None,
+                                        description: format!("{} ({})", x.description(), keymap.label(*x)),
+                                        kind: CommandKind::Action(*x)
+                                    });
+        let commands = ARGUMENT_COMMANDS.iter()
+                                        .map(|(name, argument, description, kind)| PaletteEntry {
+                                            name: name.to_string(),
+                                            argument: Some(*argument),
+                                            description: description.to_string(),
+                                            kind: *kind
+                                        });
+
+        CommandPaletteState {
+            query: RefCell::new(String::from("")),
+            entries: commands.chain(actions).collect()
+        }
+    }
+
+    // a command name followed by a space takes the rest as its argument,
+    // otherwise the query is matched against every command name
+    fn matches(self: &Self) -> Vec::<(&PaletteEntry, Option<String>)> {
+        let query = self.query.borrow();
+        let query = query.trim_start();
+
+        // longest names first so "jump tag cats" isn't read as jump to image "tag cats"
+        let mut with_argument: Vec::<&PaletteEntry> = self.entries.iter().filter(|x| x.argument.is_some()).collect();
+        with_argument.sort_by_key(|x| Reverse(x.name.len()));
+        for entry in with_argument {
+            let length = entry.name.len();
+            if query.len() > length && query.is_char_boundary(length)
+               && query[..length].eq_ignore_ascii_case(&entry.name) && query[length..].starts_with(' ') {
+                return vec![(entry, Some(query[length..].trim().to_string()))];
+            }
+        }
+
+        let mut scored: Vec::<(i32, &PaletteEntry)> = self.entries
+                                                          .iter()
+                                                          .filter_map(|x| fuzzy::score(query, &x.name).map(|s| (s, x)))
+                                                          .collect();
+        scored.sort_by(|a, b| b.0.cmp(&a.0));
+        scored.into_iter().map(|(_, x)| (x, None)).collect()
+    }
+
+    pub fn command(self: &Self) -> Result<PaletteCommand, String> {
+        let matches = self.matches();
+        let (entry, argument) = match matches.first() {
+            Some(x) => x,
+            None => return Err(format!("No command matches {}", self.query.borrow().trim()))
+        };
+
+        let argument = argument.clone().unwrap_or_default();
+        if let (Some(hint), true) = (entry.argument, argument.is_empty()) {
+            return Err(format!("Use {} {}", entry.name, hint));
+        }
+
+        match entry.kind {
+            CommandKind::Action(action) => Ok(PaletteCommand::Action(action)),
+            CommandKind::Jump => argument.parse::<usize>()
+                                         .map(PaletteCommand::Jump)
+                                         .map_err(|_| format!("{} isn't an image number", argument)),
+            CommandKind::JumpToTag => Ok(PaletteCommand::JumpToTag(argument)),
+            CommandKind::Mode => OrganizeMode::from_name(&argument)
+                                              .map(PaletteCommand::Mode)
+                                              .ok_or(format!("Unknown organize mode {}", argument)),
+            CommandKind::Sort => TagOrder::from_name(&argument)
+                                          .map(PaletteCommand::Sort)
+                                          .ok_or(format!("Unknown tag order {}", argument)),
+            CommandKind::Filter => Ok(PaletteCommand::Filter(argument)),
+            CommandKind::CreateTag => Ok(PaletteCommand::CreateTag(argument)),
+            CommandKind::ApplyTag => Ok(PaletteCommand::ApplyTag(argument)),
+            CommandKind::RenameTag => match argument.find(" to ") {
+                Some(index) => Ok(PaletteCommand::RenameTag(argument[..index].trim().to_string(),
+                                                            argument[index + 4..].trim().to_string())),
+                None => Err(format!("Use {} {}", entry.name, entry.argument.unwrap_or_default()))
+            },
+            CommandKind::DeleteTag => Ok(PaletteCommand::DeleteTag(argument))
+        }
+    }
+
+    pub fn view<'a>(self: &Self, scroll: &'a mut scrollable::State,
+                    text_input_state: &'a mut text_input::State) -> Element<'a, Message> {
+        let results = self.matches()
+                          .iter()
+                          .take(MAX_RESULTS)
+                          .enumerate()
+                          .fold(Column::<'_, Message>::new(), |c, (i, (entry, argument))| {
+                              let name = match (entry.argument, argument) {
+                                  (_, Some(argument)) => format!("{} {}", entry.name, argument),
+                                  (Some(hint), None) => format!("{} {}", entry.name, hint),
+                                  (None, None) => entry.name.to_string()
+                              };
+                              let row = Row::<'_, Message>::new()
+                                            .spacing(20)
+                                            .push(Text::new(name).width(Length::FillPortion(1)))
+                                            .push(Text::new(entry.description.to_string()).size(16).width(Length::FillPortion(1)));
+                              c.push(Container::new(row)
+                                      .width(Length::Fill)
+                                      .style(style::ImageQueueItem { is_selected: i == 0 }))
+                          });
+
+        let scrollable = Scrollable::new(scroll)
+                        .align_items(Align::Start)
+                        .push(TextInput::new(text_input_state, "Command",
+                                             &self.query.borrow(), Message::CommandPaletteChanged)
+                                        .on_submit(Message::CommandPaletteSubmitted)
+                                        .padding(10)
+                                        .size(20))
+                        .push(results);
+
+        Container::new(scrollable)
+            .width(Length::Fill)
+            .height(Length::Fill)
+            .style(style::Pane { })
+            .center_x()
+            .into()
+    }
+
+    pub fn set(self: &Self, value: String) {
+        *self.query.borrow_mut() = value;
+    }
+}
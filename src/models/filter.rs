@@ -0,0 +1,355 @@
+use crate::models::{ ImageInfo, TagId, TagRegistry };
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Word(String),
+    Quoted(String),
+    And,
+    Or,
+    Not,
+    Open,
+    Close,
+}
+
+#[derive(Debug, Clone)]
+enum Expression {
+    Tag(TagId),
+    Tagged,
+    Untagged,
+    Deleted,
+    Visited,
+    Unvisited,
+    Corrupt,
+    Not(Box<Expression>),
+    And(Box<Expression>, Box<Expression>),
+    Or(Box<Expression>, Box<Expression>),
+}
+
+// A parsed query like `cats AND NOT outdoor`, `untagged` or `(a OR b) -c`.
+// Images marked for deletion are left out unless the query asks for `deleted`.
+#[derive(Debug, Clone)]
+pub struct Filter {
+    pub text: String,
+    expression: Expression,
+    shows_deleted: bool,
+}
+
+fn tokenize(text: &str) -> Result<Vec::<Token>, String> {
+    let mut tokens = Vec::<Token>::new();
+    let mut characters = text.chars().peekable();
+    while let Some(character) = characters.next() {
+        match character {
+            _ if character.is_whitespace() => (),
+            '(' => tokens.push(Token::Open),
+            ')' => tokens.push(Token::Close),
+            '&' => tokens.push(Token::And),
+            '|' => tokens.push(Token::Or),
+            '!' | '-' => tokens.push(Token::Not),
+            '"' => {
+                let mut word = String::new();
+                loop {
+                    match characters.next() {
+                        Some('"') => break,
+                        Some(x) => word.push(x),
+                        None => return Err(String::from("Missing closing quote"))
+                    }
+                }
+                tokens.push(Token::Quoted(word));
+            }
+            _ => {
+                let mut word = character.to_string();
+                while let Some(next) = characters.peek() {
+                    if next.is_whitespace() || *next == '(' || *next == ')' {
+                        break;
+                    }
+                    word.push(*next);
+                    characters.next();
+                }
+                tokens.push(match word.to_uppercase().as_str() {
+                    "AND" => Token::And,
+                    "OR" => Token::Or,
+                    "NOT" => Token::Not,
+                    _ => Token::Word(word)
+                });
+            }
+        }
+    }
+
+    Ok(tokens)
+}
+
+struct Parser<'a> {
+    tokens: Vec::<Token>,
+    position: usize,
+    registry: &'a TagRegistry,
+    shows_deleted: bool,
+}
+
+// or := and (OR and)*
+// and := not (AND? not)*
+// not := NOT not | primary
+// primary := ( or ) | keyword | tag
+impl<'a> Parser<'a> {
+    fn peek(self: &Self) -> Option<&Token> {
+        self.tokens.get(self.position)
+    }
+
+    fn next(self: &mut Self) -> Option<Token> {
+        let token = self.tokens.get(self.position).cloned();
+        self.position = self.position + 1;
+        token
+    }
+
+    fn parse_or(self: &mut Self) -> Result<Expression, String> {
+        let mut expression = self.parse_and()?;
+        while let Some(Token::Or) = self.peek() {
+            self.next();
+            expression = Expression::Or(Box::new(expression), Box::new(self.parse_and()?));
+        }
+
+        Ok(expression)
+    }
+
+    fn parse_and(self: &mut Self) -> Result<Expression, String> {
+        let mut expression = self.parse_not()?;
+        loop {
+            match self.peek() {
+                Some(Token::And) => {
+                    self.next();
+                }
+                // terms next to each other are ANDed
+                Some(Token::Word(_)) | Some(Token::Quoted(_)) | Some(Token::Not) | Some(Token::Open) => (),
+                _ => break
+            }
+            expression = Expression::And(Box::new(expression), Box::new(self.parse_not()?));
+        }
+
+        Ok(expression)
+    }
+
+    fn parse_not(self: &mut Self) -> Result<Expression, String> {
+        if let Some(Token::Not) = self.peek() {
+            self.next();
+            return Ok(Expression::Not(Box::new(self.parse_not()?)));
+        }
+
+        self.parse_primary()
+    }
+
+    fn parse_primary(self: &mut Self) -> Result<Expression, String> {
+        match self.next() {
+            Some(Token::Open) => {
+                let expression = self.parse_or()?;
+                match self.next() {
+                    Some(Token::Close) => Ok(expression),
+                    _ => Err(String::from("Missing closing parenthesis"))
+                }
+            }
+            Some(Token::Word(word)) => match word.to_lowercase().as_str() {
+                "tagged" => Ok(Expression::Tagged),
+                "untagged" => Ok(Expression::Untagged),
+                "visited" => Ok(Expression::Visited),
+                "unvisited" => Ok(Expression::Unvisited),
+                "corrupt" => Ok(Expression::Corrupt),
+                "deleted" => {
+                    self.shows_deleted = true;
+                    Ok(Expression::Deleted)
+                }
+                _ => self.tag(&word)
+            },
+            // quotes force a tag, for names with spaces or that look like keywords
+            Some(Token::Quoted(word)) => self.tag(&word),
+            Some(token) => Err(format!("Unexpected {:?}", token)),
+            None => Err(String::from("Query ended early"))
+        }
+    }
+
+    // a full path, or a name that only one tag has
+    fn tag(self: &Self, name: &str) -> Result<Expression, String> {
+        if let Some(tag) = self.registry.find_path(name) {
+            return Ok(Expression::Tag(tag));
+        }
+
+        let named: Vec::<TagId> = self.registry.iter().filter(|x| x.name == name).map(|x| x.id).collect();
+        match named.as_slice() {
+            [tag] => Ok(Expression::Tag(*tag)),
+            [] => Err(format!("No tag named {}", name)),
+            _ => Err(format!("{} is ambiguous, use the full path", name))
+        }
+    }
+}
+
+impl Expression {
+    fn matches(self: &Self, image_info: &ImageInfo, registry: &TagRegistry) -> bool {
+        match self {
+            // a parent matches images tagged with any of its children
+            Expression::Tag(tag) => registry.implies(&image_info.tags, *tag),
+            Expression::Tagged => !image_info.tags.is_empty(),
+            Expression::Untagged => image_info.tags.is_empty(),
+            Expression::Deleted => image_info.marked_for_deletion,
+            Expression::Visited => image_info.visited,
+            Expression::Unvisited => !image_info.visited,
+            Expression::Corrupt => image_info.corrupt.is_some(),
+            Expression::Not(x) => !x.matches(image_info, registry),
+            Expression::And(x, y) => x.matches(image_info, registry) && y.matches(image_info, registry),
+            Expression::Or(x, y) => x.matches(image_info, registry) || y.matches(image_info, registry)
+        }
+    }
+}
+
+impl Filter {
+    pub fn parse(text: &str, registry: &TagRegistry) -> Result<Filter, String> {
+        let mut parser = Parser {
+            tokens: tokenize(text)?,
+            position: 0,
+            registry: registry,
+            shows_deleted: false
+        };
+        let expression = parser.parse_or()?;
+        if let Some(token) = parser.peek() {
+            return Err(format!("Unexpected {:?}", token));
+        }
+
+        Ok(Filter {
+            text: text.trim().to_string(),
+            expression: expression,
+            shows_deleted: parser.shows_deleted
+        })
+    }
+
+    pub fn shows_deleted(self: &Self) -> bool {
+        self.shows_deleted
+    }
+
+    // whether the image belongs in the filtered view
+    pub fn matches(self: &Self, image_info: &ImageInfo, registry: &TagRegistry) -> bool {
+        (self.shows_deleted || !image_info.marked_for_deletion) && self.selects(image_info, registry)
+    }
+
+    // the query alone, deleted or not
+    pub fn selects(self: &Self, image_info: &ImageInfo, registry: &TagRegistry) -> bool {
+        self.expression.matches(image_info, registry)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashSet;
+
+    fn image(tags: &[TagId]) -> ImageInfo {
+        ImageInfo {
+            path: String::from("image.jpg"),
+            companions: Vec::<String>::new(),
+            tags: tags.iter().cloned().collect::<HashSet<TagId>>(),
+            visited: false,
+            marked_for_deletion: false,
+            in_view: true,
+            hashes: None,
+            corrupt: None
+        }
+    }
+
+    struct Tags {
+        registry: TagRegistry,
+        cats: TagId,
+        dogs: TagId,
+        outdoor: TagId,
+        new_york: TagId,
+        tagged: TagId,
+    }
+
+    fn tags() -> Tags {
+        let mut registry = TagRegistry::new();
+        let cats = registry.create_path("animals/cats", None).unwrap();
+        let dogs = registry.create_path("animals/dogs", None).unwrap();
+        let outdoor = registry.create_path("outdoor", None).unwrap();
+        let new_york = registry.create_path("new york", None).unwrap();
+        // a tag with a keyword's name
+        let tagged = registry.create_path("tagged", None).unwrap();
+        Tags { registry: registry, cats: cats, dogs: dogs, outdoor: outdoor, new_york: new_york, tagged: tagged }
+    }
+
+    fn selects(query: &str, tags: &Tags, image: &ImageInfo) -> bool {
+        Filter::parse(query, &tags.registry).unwrap().matches(image, &tags.registry)
+    }
+
+    #[test]
+    fn and_binds_tighter_than_or() {
+        let tags = tags();
+        let query = "cats OR dogs outdoor";
+        assert!(selects(query, &tags, &image(&[tags.cats])));
+        assert!(!selects(query, &tags, &image(&[tags.dogs])));
+        assert!(selects(query, &tags, &image(&[tags.dogs, tags.outdoor])));
+        assert!(selects("(cats | dogs) & outdoor", &tags, &image(&[tags.dogs, tags.outdoor])));
+        assert!(!selects("(cats | dogs) & outdoor", &tags, &image(&[tags.cats])));
+    }
+
+    #[test]
+    fn negation() {
+        let tags = tags();
+        let indoor_cat = image(&[tags.cats]);
+        let outdoor_cat = image(&[tags.cats, tags.outdoor]);
+        for query in ["NOT outdoor", "!outdoor", "-outdoor", "cats -outdoor", "cats AND not outdoor"].iter() {
+            assert!(selects(query, &tags, &indoor_cat), "{}", query);
+            assert!(!selects(query, &tags, &outdoor_cat), "{}", query);
+        }
+        assert!(selects("!!outdoor", &tags, &outdoor_cat));
+        assert!(!selects("-(cats OR dogs)", &tags, &indoor_cat));
+        assert!(selects("-(cats OR dogs)", &tags, &image(&[tags.outdoor])));
+    }
+
+    #[test]
+    fn quoting() {
+        let tags = tags();
+        assert!(selects("\"new york\"", &tags, &image(&[tags.new_york])));
+        assert!(!selects("\"new york\"", &tags, &image(&[tags.outdoor])));
+        assert!(Filter::parse("\"new york", &tags.registry).is_err());
+        // unquoted it's two words, neither of which is a tag
+        assert!(Filter::parse("new york", &tags.registry).is_err());
+    }
+
+    #[test]
+    fn keywords_win_over_tags_unless_quoted() {
+        let tags = tags();
+        let cat = image(&[tags.cats]);
+        assert!(selects("tagged", &tags, &cat));
+        assert!(selects("TAGGED", &tags, &cat));
+        assert!(!selects("untagged", &tags, &cat));
+        assert!(selects("untagged", &tags, &image(&[])));
+        assert!(!selects("\"tagged\"", &tags, &cat));
+        assert!(selects("\"tagged\"", &tags, &image(&[tags.tagged])));
+    }
+
+    #[test]
+    fn tag_names_and_paths() {
+        let mut tags = tags();
+        // a parent matches images tagged with its children
+        assert!(selects("animals", &tags, &image(&[tags.dogs])));
+        assert!(selects("animals/cats", &tags, &image(&[tags.cats])));
+
+        let wild_cats = tags.registry.create_path("wild/cats", None).unwrap();
+        assert!(Filter::parse("cats", &tags.registry).is_err());
+        assert!(selects("wild/cats", &tags, &image(&[wild_cats])));
+        assert!(!selects("wild/cats", &tags, &image(&[tags.cats])));
+    }
+
+    #[test]
+    fn deleted_images_only_show_when_asked_for() {
+        let tags = tags();
+        let mut deleted = image(&[tags.cats]);
+        deleted.marked_for_deletion = true;
+        assert!(!selects("cats", &tags, &deleted));
+        assert!(selects("deleted", &tags, &deleted));
+        assert!(selects("deleted cats", &tags, &deleted));
+        assert!(Filter::parse("cats", &tags.registry).unwrap().selects(&deleted, &tags.registry));
+    }
+
+    #[test]
+    fn malformed_queries() {
+        let tags = tags();
+        for query in ["(cats", "cats )", "cats OR", "NOT", "&& cats", "birds"].iter() {
+            assert!(Filter::parse(query, &tags.registry).is_err(), "{}", query);
+        }
+    }
+}
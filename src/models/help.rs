@@ -0,0 +1,45 @@
+use iced::{ Scrollable, scrollable, Length, Container, Element, Align, Text };
+
+use crate::states::Message;
+use crate::util::keymap::{ Keymap, KeyContext };
+use crate::style;
+
+const CONTEXTS: [KeyContext; 3] = [KeyContext::Global, KeyContext::Tagging, KeyContext::SidePanel];
+
+#[derive(Debug)]
+pub struct HelpState {
+    pub sections: Vec::<(String, Vec::<String>)>,
+}
+
+impl HelpState {
+    pub fn new(keymap: &Keymap) -> HelpState {
+        HelpState {
+            sections: CONTEXTS.iter()
+                              .map(|x| (x.label().to_string(), keymap.help_lines(*x)))
+                              .collect()
+        }
+    }
+
+    pub fn view<'a>(self: &Self, scroll: &'a mut scrollable::State) -> Element<'a, Message> {
+        let scrollable = Scrollable::new(scroll)
+                        .align_items(Align::Start)
+                        .spacing(2)
+                        .push(Text::new("Keys").size(30))
+                        .push(Text::new("Unbound letters set up or apply tags").size(16));
+
+        let scrollable = self.sections
+                             .iter()
+                             .fold(scrollable, |s, (title, lines)| {
+                                 lines.iter()
+                                      .fold(s.push(Text::new(title.to_string()).size(24)),
+                                            |s, line| s.push(Text::new(line.to_string()).size(16)))
+                             });
+
+        Container::new(scrollable)
+            .width(Length::Fill)
+            .height(Length::Fill)
+            .padding(5)
+            .style(style::Pane { })
+            .into()
+    }
+}
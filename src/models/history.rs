@@ -0,0 +1,187 @@
+use serde::{ Serialize, Deserialize };
+use std::collections::HashMap;
+
+use crate::models::{ ImageInfo, Tag, TagId, TagOrder, TagRegistry };
+
+// keeps a long session from growing the history (and the session file) without bound
+const HISTORY_LIMIT: usize = 1000;
+
+// images are referred to by their index in the queue, which only changes
+// when the organize process removes trashed images and clears the history
+#[derive(Debug, Clone)]
+pub enum Action {
+    ToggleTag { image: usize, tag: TagId },
+    ToggleTags { images: Vec::<usize>, tag: TagId },
+    // children are the (tag, parent) links undoing moved up a level, for redoing to put back
+    CreateTags { tags: Vec::<Tag>, children: Vec::<(TagId, TagId)> },
+    SetDeletion { image: usize, marked: bool },
+    KeepOne { keep: usize, marked: Vec::<usize> },
+    MoveTag { tag: TagId, offset: isize, previous_order: TagOrder },
+    ChangeOrder { previous: TagOrder, next: TagOrder },
+}
+
+impl Action {
+    fn involves_tag(self: &Self, id: TagId) -> bool {
+        match self {
+            Action::ToggleTag { tag, .. } | Action::ToggleTags { tag, .. } | Action::MoveTag { tag, .. } => *tag == id,
+            Action::CreateTags { tags, .. } => tags.iter().any(|x| x.id == id),
+            _ => false
+        }
+    }
+}
+
+// tags and images are saved by path since ids and indexes don't survive a restart
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "action")]
+pub enum SavedAction {
+    ToggleTag { image: String, tag: String },
+    ToggleTags { images: Vec::<String>, tag: String },
+    CreateTags { tags: Vec::<String> },
+    SetDeletion { image: String, marked: bool },
+    KeepOne { keep: String, marked: Vec::<String> },
+    MoveTag { tag: String, offset: isize, previous_order: TagOrder },
+    ChangeOrder { previous: TagOrder, next: TagOrder },
+}
+
+#[derive(Debug, Default)]
+pub struct History {
+    undo_stack: Vec::<Action>,
+    redo_stack: Vec::<Action>,
+}
+
+impl History {
+    pub fn new() -> History {
+        History::default()
+    }
+
+    pub fn record(self: &mut Self, action: Action) {
+        self.undo_stack.push(action);
+        self.redo_stack.clear();
+        if self.undo_stack.len() > HISTORY_LIMIT {
+            self.undo_stack.remove(0);
+        }
+    }
+
+    pub fn undo(self: &mut Self) -> Option<Action> {
+        let action = self.undo_stack.pop()?;
+        self.redo_stack.push(action.clone());
+        Some(action)
+    }
+
+    pub fn redo(self: &mut Self) -> Option<Action> {
+        let action = self.redo_stack.pop()?;
+        self.undo_stack.push(action.clone());
+        Some(action)
+    }
+
+    // for an undo that finds out something its redo will need
+    pub fn amend_undone(self: &mut Self, action: Action) {
+        if let Some(last) = self.redo_stack.last_mut() {
+            *last = action;
+        }
+    }
+
+    pub fn clear(self: &mut Self) {
+        self.undo_stack.clear();
+        self.redo_stack.clear();
+    }
+
+    // removing a tag can't be undone, so nothing left in the history may refer to it
+    pub fn forget_tag(self: &mut Self, id: TagId) {
+        self.undo_stack.retain(|x| !x.involves_tag(id));
+        self.redo_stack.retain(|x| !x.involves_tag(id));
+    }
+
+    // only the undo stack is saved, redoing across sessions would need tags that no longer exist
+    pub fn to_saved(self: &Self, registry: &TagRegistry, image_infos: &[ImageInfo]) -> Vec::<SavedAction> {
+        let image_path = |index: &usize| image_infos.get(*index).map(|x| x.path.clone());
+        let tag_path = |id: &TagId| registry.get(*id).map(|_| registry.path(*id));
+
+        self.undo_stack
+            .iter()
+            .filter_map(|action| match action {
+                Action::ToggleTag { image, tag } => Some(SavedAction::ToggleTag {
+                    image: image_path(image)?,
+                    tag: tag_path(tag)?
+                }),
+                Action::ToggleTags { images, tag } => Some(SavedAction::ToggleTags {
+                    images: images.iter().filter_map(|x| image_path(x)).collect(),
+                    tag: tag_path(tag)?
+                }),
+                Action::CreateTags { tags, .. } => Some(SavedAction::CreateTags {
+                    tags: tags.iter().filter_map(|x| tag_path(&x.id)).collect()
+                }),
+                Action::SetDeletion { image, marked } => Some(SavedAction::SetDeletion {
+                    image: image_path(image)?,
+                    marked: *marked
+                }),
+                Action::KeepOne { keep, marked } => Some(SavedAction::KeepOne {
+                    keep: image_path(keep)?,
+                    marked: marked.iter().filter_map(|x| image_path(x)).collect()
+                }),
+                Action::MoveTag { tag, offset, previous_order } => Some(SavedAction::MoveTag {
+                    tag: tag_path(tag)?,
+                    offset: *offset,
+                    previous_order: *previous_order
+                }),
+                Action::ChangeOrder { previous, next } => Some(SavedAction::ChangeOrder {
+                    previous: *previous,
+                    next: *next
+                })
+            })
+            .collect()
+    }
+
+    // actions referring to images or tags that are gone are dropped
+    pub fn from_saved(saved: &[SavedAction], registry: &TagRegistry, image_infos: &[ImageInfo]) -> History {
+        let indexes: HashMap<&str, usize> = image_infos.iter()
+                                                       .enumerate()
+                                                       .map(|(i, x)| (x.path.as_str(), i))
+                                                       .collect();
+        let image_index = |path: &String| indexes.get(path.as_str()).cloned();
+
+        let mut history = History::new();
+        history.undo_stack = saved.iter()
+                                  .filter_map(|action| match action {
+                                      SavedAction::ToggleTag { image, tag } => Some(Action::ToggleTag {
+                                          image: image_index(image)?,
+                                          tag: registry.find_path(tag)?
+                                      }),
+                                      SavedAction::ToggleTags { images, tag } => Some(Action::ToggleTags {
+                                          images: images.iter().filter_map(|x| image_index(x)).collect(),
+                                          tag: registry.find_path(tag)?
+                                      }),
+                                      SavedAction::CreateTags { tags } => {
+                                          let tags: Vec::<Tag> = tags.iter()
+                                                                     .filter_map(|x| registry.find_path(x))
+                                                                     .filter_map(|x| registry.get(x).cloned())
+                                                                     .collect();
+                                          if tags.is_empty() {
+                                              None
+                                          } else {
+                                              Some(Action::CreateTags { tags: tags, children: Vec::<(TagId, TagId)>::new() })
+                                          }
+                                      }
+                                      SavedAction::SetDeletion { image, marked } => Some(Action::SetDeletion {
+                                          image: image_index(image)?,
+                                          marked: *marked
+                                      }),
+                                      SavedAction::KeepOne { keep, marked } => Some(Action::KeepOne {
+                                          keep: image_index(keep)?,
+                                          marked: marked.iter().filter_map(|x| image_index(x)).collect()
+                                      }),
+                                      SavedAction::MoveTag { tag, offset, previous_order } => Some(Action::MoveTag {
+                                          tag: registry.find_path(tag)?,
+                                          offset: *offset,
+                                          previous_order: *previous_order
+                                      }),
+                                      SavedAction::ChangeOrder { previous, next } => Some(Action::ChangeOrder {
+                                          previous: *previous,
+                                          next: *next
+                                      })
+                                  })
+                                  .collect();
+
+        history
+    }
+}
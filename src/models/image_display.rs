@@ -1,35 +1,119 @@
-use iced::{ Length, Container, Column, Row, Element, Align, Text };
-use std::{ cell::RefCell, rc::Rc };
+use iced::{ image::Handle, Length, Container, Column, Row, Element, Align, Text, Image };
+use image::RgbaImage;
+use std::{ cell::RefCell, rc::Rc, time::Duration };
 
 use crate::states::Message;
-use crate::util;
+use crate::models::{ TagId, TagRegistry };
+use crate::util::{ self, histogram::Histogram, image::ImageContent };
 
 #[derive(Debug)]
 pub struct ImageDisplayState {
     pub root_path: Rc::<RefCell::<String>>,
     pub current_image_path: String,
-    pub current_image_tags: Option<Vec::<char>>,
+    // decoded once per image, RAW previews are too slow to pull out on every redraw
+    pub image: Option<ImageContent>,
+    pub image_error: Option<String>,
+    pub page: usize,
+    pub page_count: usize,
+    pub frame: usize,
+    pub playing: bool,
+    // set by the integrity scan, shown instead of whatever part of the image decodes
+    pub corrupt: Option<String>,
+    pub histogram: Option<Histogram>,
+    pub histogram_image: Option<Handle>,
+    // kept so the clipping overlay can be made without decoding the image again
+    pub pixels: Option<RgbaImage>,
+    // the image with clipped highlights and shadows marked, only made while show_clipping is on
+    pub clipping: Option<Handle>,
+    pub show_clipping: bool,
+    pub current_image_tags: Option<Vec::<TagId>>,
+    pub duplicates: Vec::<String>,
+    pub cluster_position: Option<(usize, usize)>,
+    pub tags: Rc::<RefCell::<TagRegistry>>,
 }
 
 impl ImageDisplayState {
+    fn frame_count(self: &Self) -> usize {
+        match &self.image {
+            Some(ImageContent::Animation(frames)) => frames.len(),
+            _ => 0
+        }
+    }
+
+    // how long the current frame shows for while an animation plays
+    pub fn frame_delay(self: &Self) -> Option<Duration> {
+        match &self.image {
+            Some(ImageContent::Animation(frames)) if self.playing => frames.get(self.frame).map(|x| x.delay),
+            _ => None
+        }
+    }
+
+    // moves through the frames, wrapping around at either end
+    pub fn step_frame(self: &mut Self, step: isize) {
+        let count = self.frame_count() as isize;
+        if count > 0 {
+            self.frame = (self.frame as isize + step).rem_euclid(count) as usize;
+        }
+    }
+
+    // returns whether there was an animation to play or pause
+    pub fn toggle_playing(self: &mut Self) -> bool {
+        if self.frame_count() == 0 {
+            return false;
+        }
+        self.playing = !self.playing;
+        true
+    }
+
     pub fn view<'a>(self: &'a Self) -> Element<'a, Message> {
-        let mut tag_row = Row::<'_, Message>::new();
+        let mut tag_row = Row::<'_, Message>::new().spacing(10);
+        let registry = self.tags.borrow();
         match &self.current_image_tags {
             Some(tags) => tag_row = tags.iter()
                                         .fold(tag_row, |r, tag| {
-                                            let column = Column::<'_, Message>::new()
-                                                             .push(Text::new(tag.to_string()));
-                                            r.push(Container::new(column)
-                                             .width(Length::Shrink)
-                                             .height(Length::Shrink))
+                                            r.push(util::badge::tag_badge(registry.path(*tag),
+                                                                          registry.color(*tag), 20))
                                         }),
             _ => ()
         }
 
-        let column = Column::<'_, Message>::new()
-                            .align_items(Align::Start)
-                            .push(util::image::load_image(self.current_image_path.clone()))
-                            .push(tag_row);
+        let mut column = Column::<'_, Message>::new().align_items(Align::Start);
+        match (&self.corrupt, &self.clipping, &self.image, &self.image_error) {
+            (Some(error), _, _, _) => column = column.push(Text::new(format!("{} is corrupt or truncated: {}",
+                                                                             self.current_image_path, error)).size(16)),
+            (None, Some(overlay), _, _) => {
+                column = column.push(util::image::load_image(&ImageContent::Raster(overlay.clone()), 0))
+            }
+            (None, None, Some(content), _) => column = column.push(util::image::load_image(content, self.frame)),
+            (None, None, None, Some(error)) => column = column.push(Text::new(format!("Can't display {}: {}",
+                                                                                      self.current_image_path, error)).size(16)),
+            _ => ()
+        }
+        column = column.push(tag_row);
+        if let (Some(histogram), Some(handle)) = (&self.histogram, &self.histogram_image) {
+            let clipped = Column::<'_, Message>::new()
+                            .spacing(5)
+                            .push(Text::new(format!("Highlights clipped {:.1}%", histogram.highlights * 100.0)).size(14))
+                            .push(Text::new(format!("Shadows clipped {:.1}%", histogram.shadows * 100.0)).size(14));
+            column = column.push(Row::<'_, Message>::new()
+                                    .spacing(10)
+                                    .align_items(Align::Center)
+                                    .push(Image::new(handle.clone()).width(Length::Units(256)))
+                                    .push(clipped));
+        }
+        if self.frame_count() > 0 {
+            let paused = if self.playing { "" } else { " (paused)" };
+            column = column.push(Text::new(format!("Frame {} of {}{}", self.frame + 1, self.frame_count(), paused)).size(16));
+        }
+        if self.page_count > 1 {
+            column = column.push(Text::new(format!("Page {} of {}", self.page + 1, self.page_count)).size(16));
+        }
+        if !self.duplicates.is_empty() {
+            column = column.push(Text::new(format!("Identical to {}", self.duplicates.join(", "))).size(16));
+        }
+        if let Some((position, size)) = self.cluster_position {
+            column = column.push(Text::new(format!("Similar image {} of {}", position, size)).size(16));
+        }
 
         Container::new(column)
             .width(Length::Fill)
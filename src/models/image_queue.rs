@@ -1,70 +1,434 @@
 use iced::{ Scrollable, scrollable, Length, 
             Column, Row, Container, Element, Align, Text };
-use std::{ cmp, collections::HashMap };
+use std::{ cmp, collections::HashSet, cell::RefCell, rc::Rc };
 
 use crate::states::Message;
-use crate::util;
+use crate::models::{ Filter, Stack, TagId, TagRegistry };
+use crate::util::{ self, perceptual_hash::ImageHashes };
 use crate::style;
+use crate::lib_ext::*;
 
 #[derive(Debug)]
 pub struct ImageQueueState {
     pub selected_image_index: usize,
     pub image_infos: Vec::<ImageInfo>,
+    pub tags: Rc::<RefCell::<TagRegistry>>,
+    pub deletion_history: Vec::<usize>,
+    pub filter: Option<Filter>,
+    pub duplicate_groups: Vec::<Vec::<String>>,
+    pub similar_clusters: Vec::<Vec::<String>>,
+    pub stacks: Vec::<Stack>,
 }
 
 #[derive(Debug)]
 pub struct ImageInfo {
     pub path: String,
-    pub tags: HashMap<char, ()>
+    // files shot alongside the displayed one, like a RAW or a sidecar, that go wherever it goes
+    pub companions: Vec::<String>,
+    pub tags: HashSet<TagId>,
+    pub visited: bool,
+    pub marked_for_deletion: bool,
+    pub in_view: bool,
+    pub hashes: Option<ImageHashes>,
+    // the decoder's error when the integrity scan found the file damaged or cut short
+    pub corrupt: Option<String>,
+}
+
+impl ImageInfo {
+    pub fn is_visible(self: &Self) -> bool {
+        self.in_view
+    }
+
+    // the displayed file and its companions
+    pub fn files(self: &Self) -> Vec::<String> {
+        std::iter::once(&self.path).chain(self.companions.iter()).cloned().collect()
+    }
 }
 
 impl ImageQueueState {
-    pub fn new() -> ImageQueueState {
-        let mut image_paths = util::file_io::get_directory_list(&".")
+    pub fn new(tags: Rc::<RefCell::<TagRegistry>>) -> ImageQueueState {
+        let image_paths = util::file_io::get_directory_list(&".")
                                 .unwrap_or(Vec::<String>::new());
-        image_paths.sort_unstable();
+        let items = util::companions::group(image_paths);
 
         ImageQueueState { 
             selected_image_index: 0,
-            image_infos: image_paths.iter()
-                                    .map(|x| 
-                                        ImageInfo { 
-                                            path: x[2..].to_string(), // ignore "./" in path
-                                            tags: HashMap::<char, ()>::new() 
-                                        }
-                                    )
-                                    .collect()
+            image_infos: items.iter()
+                              .map(|(x, companions)| 
+                                  ImageInfo { 
+                                      path: x[2..].to_string(), // ignore "./" in path
+                                      companions: companions.iter().map(|x| x[2..].to_string()).collect(),
+                                      tags: HashSet::<TagId>::new(),
+                                      visited: false,
+                                      marked_for_deletion: false,
+                                      in_view: true,
+                                      hashes: None,
+                                      corrupt: None
+                                  }
+                              )
+                              .collect(),
+            tags: tags,
+            deletion_history: Vec::<usize>::new(),
+            filter: None,
+            duplicate_groups: Vec::<Vec::<String>>::new(),
+            similar_clusters: Vec::<Vec::<String>>::new(),
+            stacks: Vec::<Stack>::new()
+        }
+    }
+
+    // Only images matching the filter are shown and navigated to. Which images match
+    // is worked out here rather than on every key, so tagging an image doesn't pull
+    // it out from under you; setting the filter again picks up the new tags.
+    pub fn set_filter(self: &mut Self, filter: Option<Filter>) -> usize {
+        self.filter = filter;
+        self.refresh_filter()
+    }
+
+    pub fn refresh_filter(self: &mut Self) -> usize {
+        let indexes: Vec::<usize> = (0..self.image_infos.len()).collect();
+        self.update_visibility(&indexes);
+
+        if self.current().is_none() && !self.select_next(|_| true) {
+            self.select_prev(|_| true);
+        }
+
+        self.image_infos.iter().filter(|x| x.is_visible()).count()
+    }
+
+    // whether the filter selects each image and no collapsed stack hides it
+    fn update_visibility(self: &mut Self, indexes: &[usize]) {
+        let hidden: HashSet<&String> = self.stacks.iter().flat_map(|x| x.hidden()).collect();
+        let registry = self.tags.borrow();
+        for index in indexes.iter() {
+            if let Some(image_info) = self.image_infos.get_mut(*index) {
+                let matches = match &self.filter {
+                    Some(filter) => filter.matches(image_info, &registry),
+                    None => !image_info.marked_for_deletion
+                };
+                image_info.in_view = matches && !hidden.contains(&image_info.path);
+            }
+        }
+    }
+
+    pub fn visit_current(self: &mut Self) {
+        if let Some(image_info) = self.image_infos.get_mut(self.selected_image_index) {
+            image_info.visited = true;
+        }
+    }
+
+    pub fn current(self: &Self) -> Option<&ImageInfo> {
+        self.image_infos
+            .get(self.selected_image_index)
+            .filter(|x| x.is_visible())
+    }
+
+    pub fn select_next<F>(self: &mut Self, predicate: F) -> bool where F: Fn (&ImageInfo) -> bool {
+        self.select_next_nth(1, predicate)
+    }
+
+    pub fn select_prev<F>(self: &mut Self, predicate: F) -> bool where F: Fn (&ImageInfo) -> bool {
+        self.select_prev_nth(1, predicate)
+    }
+
+    pub fn select_next_nth<F>(self: &mut Self, count: usize, predicate: F) -> bool where F: Fn (&ImageInfo) -> bool {
+        let index = self.image_infos.next_nth(self.selected_image_index, count, |x| x.is_visible() && predicate(x));
+        self.select(index)
+    }
+
+    pub fn select_prev_nth<F>(self: &mut Self, count: usize, predicate: F) -> bool where F: Fn (&ImageInfo) -> bool {
+        let index = self.image_infos.prev_nth(self.selected_image_index, count, |x| x.is_visible() && predicate(x));
+        self.select(index)
+    }
+
+    pub fn select_first(self: &mut Self) -> bool {
+        let index = self.image_infos.first_where(|x| x.is_visible());
+        self.select(index)
+    }
+
+    pub fn select_last(self: &mut Self) -> bool {
+        let index = self.image_infos.last_where(|x| x.is_visible());
+        self.select(index)
+    }
+
+    // numbered from 1 in the order the queue shows them, past the end selects the last image
+    pub fn select_number(self: &mut Self, number: usize) -> bool {
+        let index = self.image_infos.nth_where(number.saturating_sub(1), |x| x.is_visible());
+        match index {
+            Some(_) => self.select(index),
+            None => self.select_last()
+        }
+    }
+
+    fn select(self: &mut Self, index: Option<usize>) -> bool {
+        match index {
+            Some(x) => {
+                self.selected_image_index = x;
+                true
+            }
+            None => false
+        }
+    }
+
+    // hides the current image until the organize process runs, where it gets trashed
+    pub fn mark_current_for_deletion(self: &mut Self) -> Option<usize> {
+        let index = self.selected_image_index;
+        self.current()?;
+        self.set_marked(index, true);
+
+        Some(index)
+    }
+
+    pub fn restore_last_deleted(self: &mut Self) -> Option<usize> {
+        let index = *self.deletion_history.last()?;
+        self.set_marked(index, false);
+
+        Some(index)
+    }
+
+    pub fn set_marked(self: &mut Self, index: usize, marked: bool) {
+        let path = match self.image_infos.get_mut(index) {
+            Some(image_info) => {
+                image_info.marked_for_deletion = marked;
+                image_info.path.clone()
+            }
+            None => return
+        };
+
+        // a collapsed stack stays in the queue while it has shots left, the next unmarked
+        // one standing in for it, and a restored shot stands in for it again
+        let image_infos = &self.image_infos;
+        let is_unmarked = |path: &String| image_infos.iter().any(|x| x.path == *path && !x.marked_for_deletion);
+        let mut members = vec![path.clone()];
+        if let Some(stack) = self.stacks.iter_mut().find(|x| !x.expanded && x.paths.contains(&path)) {
+            let position = stack.paths.iter().position(|x| *x == path).unwrap_or(0);
+            let next = stack.paths
+                            .iter()
+                            .cycle()
+                            .skip(position + 1)
+                            .take(stack.paths.len())
+                            .find(|x| is_unmarked(x))
+                            .cloned();
+            match (marked, next) {
+                (true, Some(next)) if stack.pick == path => stack.pick = next,
+                (false, _) => stack.pick = path.clone(),
+                _ => ()
+            }
+            members = stack.paths.clone();
+        }
+        let changed = self.indexes_of(&members);
+        self.update_visibility(&changed);
+
+        self.deletion_history.retain(|x| *x != index);
+        if marked {
+            self.deletion_history.push(index);
+            if index == self.selected_image_index && self.current().is_none() {
+                let pick = changed.iter().cloned().find(|x| self.image_infos[*x].is_visible());
+                match pick {
+                    Some(pick) => self.selected_image_index = pick,
+                    None => if !self.select_next(|_| true) {
+                        self.select_prev(|_| true);
+                    }
+                }
+            }
+        } else if self.image_infos[index].is_visible() {
+            self.selected_image_index = index;
+        }
+    }
+
+    // drops images that are gone from the folder, like the ones trashed by the organize process
+    pub fn remove_paths(self: &mut Self, paths: &HashSet<String>) {
+        let selected_path = self.current().map(|x| x.path.clone());
+        // images outside a filtered organize run stay marked, so keep their history by path
+        let history: Vec::<String> = self.deletion_history
+                                         .iter()
+                                         .filter_map(|x| self.image_infos.get(*x))
+                                         .filter(|x| !paths.contains(&x.path))
+                                         .map(|x| x.path.clone())
+                                         .collect();
+        self.image_infos.retain(|x| !paths.contains(&x.path));
+        for group in self.duplicate_groups.iter_mut() {
+            group.retain(|x| !paths.contains(x));
+        }
+        self.duplicate_groups.retain(|x| x.len() > 1);
+        for cluster in self.similar_clusters.iter_mut() {
+            cluster.retain(|x| !paths.contains(x));
+        }
+        self.similar_clusters.retain(|x| x.len() > 1);
+        for stack in self.stacks.iter_mut() {
+            stack.paths.retain(|x| !paths.contains(x));
+            if paths.contains(&stack.pick) {
+                if let Some(first) = stack.paths.first() {
+                    stack.pick = first.clone();
+                }
+            }
+        }
+        self.stacks.retain(|x| x.paths.len() > 1);
+        self.deletion_history = history.iter()
+                                       .filter_map(|path| self.image_infos.iter().position(|x| x.path == *path))
+                                       .collect();
+        self.selected_image_index = selected_path.and_then(|path| self.image_infos.iter().position(|x| x.path == path))
+                                                 .unwrap_or(0);
+        // a stack that lost its pick shows its next image instead
+        self.refresh_filter();
+        if self.current().is_none() {
+            self.select_first();
+        }
+    }
+
+    // groups are kept by path so they survive images being removed from the queue
+    fn group_paths(self: &Self, groups: &[Vec::<usize>]) -> Vec::<Vec::<String>> {
+        groups.iter()
+              .map(|x| x.iter().map(|index| self.image_infos[*index].path.clone()).collect())
+              .collect()
+    }
+
+    fn indexes_of(self: &Self, paths: &[String]) -> Vec::<usize> {
+        paths.iter()
+             .filter_map(|x| self.image_infos.iter().position(|image_info| image_info.path == *x))
+             .collect()
+    }
+
+    // every image in the same group as the image at index, itself included
+    fn group_of(self: &Self, groups: &[Vec::<String>], index: usize) -> Vec::<usize> {
+        let path = match self.image_infos.get(index) {
+            Some(image_info) => &image_info.path,
+            None => return Vec::<usize>::new()
+        };
+
+        match groups.iter().find(|x| x.contains(path)) {
+            Some(group) => self.indexes_of(group),
+            None => Vec::<usize>::new()
+        }
+    }
+
+    pub fn set_duplicates(self: &mut Self, groups: &[Vec::<usize>]) {
+        self.duplicate_groups = self.group_paths(groups);
+    }
+
+    // the other images with the same contents as the image at index
+    pub fn duplicates_of(self: &Self, index: usize) -> Vec::<usize> {
+        self.group_of(&self.duplicate_groups, index).into_iter().filter(|x| *x != index).collect()
+    }
+
+    pub fn set_clusters(self: &mut Self, clusters: &[Vec::<usize>]) {
+        self.similar_clusters = self.group_paths(clusters);
+    }
+
+    // a cluster for one image replaces the clusters any of its images were in
+    pub fn add_cluster(self: &mut Self, cluster: &[usize]) {
+        let cluster = self.group_paths(&[cluster.to_vec()]).remove(0);
+        for existing in self.similar_clusters.iter_mut() {
+            existing.retain(|x| !cluster.contains(x));
         }
+        self.similar_clusters.retain(|x| x.len() > 1);
+        self.similar_clusters.push(cluster);
+    }
+
+    pub fn cluster_of(self: &Self, index: usize) -> Vec::<usize> {
+        self.group_of(&self.similar_clusters, index)
+    }
+
+    // new stacks start collapsed on their first image
+    pub fn set_stacks(self: &mut Self, bursts: &[Vec::<usize>]) {
+        self.stacks = self.group_paths(bursts).into_iter().map(Stack::new).collect();
+        self.refresh_filter();
+    }
+
+    pub fn stack_of(self: &Self, index: usize) -> Option<&Stack> {
+        let path = &self.image_infos.get(index)?.path;
+        self.stacks.iter().find(|x| x.paths.contains(path))
+    }
+
+    fn stack_of_mut(self: &mut Self, index: usize) -> Option<&mut Stack> {
+        let path = self.image_infos.get(index)?.path.clone();
+        self.stacks.iter_mut().find(|x| x.paths.contains(&path))
     }
 
-    pub fn delete_current(self: &mut Self) {
-        if self.selected_image_index < self.image_infos.len() {
-            self.image_infos.remove(self.selected_image_index);
-            if self.selected_image_index == self.image_infos.len() {
-                self.selected_image_index = self.selected_image_index - 1;
-            } 
+    pub fn stack_members(self: &Self, index: usize) -> Vec::<usize> {
+        match self.stack_of(index) {
+            Some(stack) => self.indexes_of(&stack.paths),
+            None => Vec::<usize>::new()
         }
     }
 
+    // collapsing goes back to the pick. Returns whether the stack is now expanded.
+    pub fn toggle_stack(self: &mut Self) -> Option<bool> {
+        let index = self.selected_image_index;
+        let stack = self.stack_of_mut(index)?;
+        stack.expanded = !stack.expanded;
+        let (expanded, pick) = (stack.expanded, stack.pick.clone());
+        if !expanded {
+            if let Some(pick) = self.image_infos.iter().position(|x| x.path == pick) {
+                self.selected_image_index = pick;
+            }
+        }
+        self.refresh_filter();
+
+        Some(expanded)
+    }
+
+    // results of the integrity scan come back by path, the queue may have changed since it started
+    pub fn set_corrupt(self: &mut Self, path: &str, error: Option<String>) {
+        if let Some(image_info) = self.image_infos.iter_mut().find(|x| x.path == path) {
+            image_info.corrupt = error;
+        }
+    }
+
+    // the current image stands in for its stack from now on
+    pub fn set_pick(self: &mut Self) -> Option<String> {
+        let index = self.selected_image_index;
+        let path = self.image_infos.get(index)?.path.clone();
+        self.stack_of_mut(index)?.pick = path.clone();
+
+        Some(path)
+    }
+
     pub fn view<'a>(self: &Self, scroll: &'a mut scrollable::State) -> Element<'a, Message> {
         let mut row = Row::<'_, Message>::new();
 
-        let start = if self.selected_image_index < 3 { 0 } else { self.selected_image_index - 2 };
-        let end = cmp::min(self.image_infos.len(), self.selected_image_index + 10);
-        let mut item_index: usize = start;
+        let visible: Vec::<usize> = (0..self.image_infos.len()).filter(|x| self.image_infos[*x].is_visible())
+                                                                .collect();
+        let selected = visible.iter().position(|x| *x >= self.selected_image_index).unwrap_or(0);
+        let start = if selected < 3 { 0 } else { selected - 2 };
+        let end = cmp::min(visible.len(), selected + 10);
+        let registry = self.tags.borrow();
 
         // in lieu of horizontal scrolling, show a shifting window of directory
-        row = self.image_infos[start..end]
+        row = visible[start..end]
                       .iter()
-                      .fold(row, |r, image_info| {
-                          let text = Text::new(image_info.path.to_string());
-                          let column = Column::<'_, Message>::new().push(text);
+                      .fold(row, |r, item_index| {
+                          let image_info = &self.image_infos[*item_index];
+                          let mut name = image_info.path.to_string();
+                          if !image_info.companions.is_empty() {
+                              let extensions: Vec::<String> = image_info.companions
+                                                                        .iter()
+                                                                        .map(|x| util::companions::extension(x).to_uppercase())
+                                                                        .collect();
+                              name.push_str(&format!(" + {}", extensions.join(", ")));
+                          }
+                          let text = Text::new(name);
+                          let mut tag_ids: Vec::<&TagId> = image_info.tags.iter().collect();
+                          tag_ids.sort_unstable();
+                          let badges = tag_ids.iter().fold(Row::<'_, Message>::new().spacing(2), |r, tag| {
+                              r.push(util::badge::tag_badge(registry.name(**tag), registry.color(**tag), 12))
+                          });
+                          let mut column = Column::<'_, Message>::new().push(text).push(badges);
+                          if let Some(stack) = self.stack_of(*item_index) {
+                              let label = match stack.expanded {
+                                  true => format!("Stack {} of {}",
+                                                  stack.paths.iter().position(|x| *x == image_info.path).unwrap_or(0) + 1,
+                                                  stack.paths.len()),
+                                  false => format!("Stack of {}", stack.paths.len())
+                              };
+                              column = column.push(Text::new(label).size(12));
+                          }
+                          if image_info.corrupt.is_some() {
+                              column = column.push(Text::new("Corrupt").size(12));
+                          }
                           let style = style::ImageQueueItem {
-                              is_selected: item_index == self.selected_image_index
+                              is_selected: *item_index == self.selected_image_index
                           };
 
-                          item_index = item_index + 1;
-
                           r.push(Container::new(column)
                            .width(Length::Shrink)
                            .height(Length::Fill)
@@ -1,9 +1,25 @@
+mod command_palette;
+mod filter;
+mod help;
+mod history;
 mod image_display;
 mod image_queue;
+mod session_stats;
 mod side_panel;
+mod stack;
+mod tag;
 mod tag_input;
+mod tag_picker;
 
+pub use command_palette::*;
+pub use filter::*;
+pub use help::*;
+pub use history::*;
 pub use image_display::*;
 pub use image_queue::*;
+pub use session_stats::*;
 pub use side_panel::*;
+pub use stack::*;
+pub use tag::*;
 pub use tag_input::*;
+pub use tag_picker::*;
@@ -0,0 +1,82 @@
+use std::{ collections::HashMap, time::{ Duration, Instant } };
+
+use crate::models::{ ImageInfo, TagId, TagRegistry };
+
+#[derive(Debug)]
+pub struct SessionStats {
+    pub started: Instant,
+    pub initial_tagged: usize,
+}
+
+#[derive(Debug, Default)]
+pub struct TagStatistics {
+    pub tag_counts: HashMap<TagId, usize>,
+    pub tagged: usize,
+    pub untagged: usize,
+    pub visited: usize,
+    pub deleted: usize,
+    pub tagged_this_session: usize,
+    pub images_per_minute: Option<f64>,
+    pub remaining: Option<Duration>,
+}
+
+impl TagStatistics {
+    pub fn total(self: &Self) -> usize {
+        self.tagged + self.untagged
+    }
+}
+
+impl SessionStats {
+    pub fn new(image_infos: &[ImageInfo]) -> SessionStats {
+        SessionStats {
+            started: Instant::now(),
+            initial_tagged: image_infos.iter().filter(|x| !x.marked_for_deletion && !x.tags.is_empty()).count()
+        }
+    }
+
+    pub fn compute(self: &Self, image_infos: &[ImageInfo], registry: &TagRegistry) -> TagStatistics {
+        let mut statistics = TagStatistics::default();
+        for image_info in image_infos.iter() {
+            if image_info.marked_for_deletion {
+                statistics.deleted = statistics.deleted + 1;
+                continue;
+            }
+
+            if image_info.tags.is_empty() {
+                statistics.untagged = statistics.untagged + 1;
+            } else {
+                statistics.tagged = statistics.tagged + 1;
+            }
+            if image_info.visited {
+                statistics.visited = statistics.visited + 1;
+            }
+
+            // parents count every image tagged with one of their children
+            for tag in registry.expand(&image_info.tags) {
+                *statistics.tag_counts.entry(tag).or_insert(0) += 1;
+            }
+        }
+
+        statistics.tagged_this_session = statistics.tagged.saturating_sub(self.initial_tagged);
+
+        let minutes = self.started.elapsed().as_secs_f64() / 60.0;
+        if statistics.tagged_this_session > 0 && minutes > 0.0 {
+            let images_per_minute = statistics.tagged_this_session as f64 / minutes;
+            statistics.images_per_minute = Some(images_per_minute);
+            statistics.remaining = Some(Duration::from_secs_f64(statistics.untagged as f64 / images_per_minute * 60.0));
+        }
+
+        statistics
+    }
+}
+
+pub fn format_duration(duration: Duration) -> String {
+    let seconds = duration.as_secs();
+    if seconds >= 3600 {
+        format!("{}h {:02}m", seconds / 3600, (seconds % 3600) / 60)
+    } else if seconds >= 60 {
+        format!("{}m {:02}s", seconds / 60, seconds % 60)
+    } else {
+        format!("{}s", seconds)
+    }
+}
@@ -1,38 +1,69 @@
-use iced::{ Scrollable, scrollable, Length, 
+use iced::{ Scrollable, scrollable, Length,
             Row, Container, Element, Align, Text };
-use std::{ collections::HashMap, cell::RefCell };
+use std::{ cell::RefCell, rc::Rc };
 
 use crate::states::Message;
+use crate::models::{ TagRegistry, TagStatistics, format_duration };
 use crate::style;
+use crate::util;
 
 #[derive(Debug)]
-pub struct SidePanelState { 
+pub struct SidePanelState {
     pub label: String,
-    pub tags: RefCell<HashMap<String, String>>
+    pub tags: Rc::<RefCell::<TagRegistry>>,
+    pub selected_tag_index: Option<usize>,
+    pub is_rebinding: bool,
+    pub statistics: TagStatistics,
+    pub help: Vec::<String>,
 }
 
 impl SidePanelState {
-    pub fn insert(self: &Self, tag: String, value: String) {
-        self.tags.borrow_mut()
-                 .insert(tag, value);
-    }
-
     pub fn view<'a>(self: &Self, scroll: &'a mut scrollable::State) -> Element<'a, Message> {
         let mut scrollable = Scrollable::new(scroll)
             .align_items(Align::Start)
-            .push(Text::new(self.label.to_string()).size(30));
+            .push(Text::new(format!("{} ({})", self.label, self.tags.borrow().order.label())).size(30));
 
-        for x in self.tags.borrow().iter() {
-            let mut viewable_text = String::from(x.0);
-            viewable_text.push_str(" - "); 
-            viewable_text.push_str(x.1); 
-            let text = Text::new(viewable_text);
-            scrollable = scrollable.push(Row::<'_, Message>::new()
-                                                    .push(Container::new(text)
+        for (index, (depth, tag)) in self.tags.borrow().tree().iter().enumerate() {
+            let is_selected = self.selected_tag_index == Some(index);
+            let key = match tag.key {
+                _ if is_selected && self.is_rebinding => String::from("?"),
+                Some(key) => key.to_string(),
+                None => String::from(" ")
+            };
+            let count = self.statistics.tag_counts.get(&tag.id).unwrap_or(&0);
+            let text = Text::new(format!("{}{} ({})", "    ".repeat(*depth), tag.name, count));
+            scrollable = scrollable.push(Container::new(Row::<'_, Message>::new()
+                                                            .spacing(5)
+                                                            .push(util::badge::tag_badge(key, tag.color, 16))
+                                                            .push(text))
                                                     .width(Length::Fill)
-                                                    .height(Length::Shrink)));
+                                                    .height(Length::Shrink)
+                                                    .style(style::ImageQueueItem { is_selected: is_selected }));
+        }
+
+        let statistics = &self.statistics;
+        let mut summary = vec![
+            format!("Tagged: {}", statistics.tagged),
+            format!("Untagged: {}", statistics.untagged),
+            format!("Deleted: {}", statistics.deleted),
+            format!("Tagged this session: {}", statistics.tagged_this_session),
+        ];
+        if let Some(images_per_minute) = statistics.images_per_minute {
+            summary.push(format!("Images per minute: {:.1}", images_per_minute));
         }
-            
+        if let Some(remaining) = statistics.remaining {
+            summary.push(format!("Time remaining: {}", format_duration(remaining)));
+        }
+        scrollable = summary.into_iter()
+                            .fold(scrollable.push(Text::new("Session").size(24)),
+                                  |s, line| s.push(Text::new(line).size(16)));
+
+        if self.selected_tag_index.is_some() {
+            scrollable = self.help
+                             .iter()
+                             .fold(scrollable, |s, line| s.push(Text::new(line.to_string()).size(14)));
+        }
+
         Container::new(scrollable)
             .width(Length::Fill)
             .height(Length::Fill)
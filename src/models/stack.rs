@@ -0,0 +1,52 @@
+// seconds between shots for them to count as one burst
+pub const DEFAULT_BURST_GAP: f64 = 2.0;
+
+// A burst of shots that shows up as one item in the queue, the pick standing in
+// for the rest until the stack is expanded. Kept by path like the other groups.
+#[derive(Debug, Clone)]
+pub struct Stack {
+    pub paths: Vec::<String>,
+    pub pick: String,
+    pub expanded: bool,
+}
+
+impl Stack {
+    pub fn new(paths: Vec::<String>) -> Stack {
+        Stack {
+            pick: paths[0].clone(),
+            paths: paths,
+            expanded: false
+        }
+    }
+
+    // images next to each other in the queue taken within gap seconds of the one before,
+    // an image with no time ends the burst
+    pub fn find_bursts(times: &[Option<f64>], gap: f64) -> Vec::<Vec::<usize>> {
+        let mut bursts = Vec::<Vec::<usize>>::new();
+        let mut current = Vec::<usize>::new();
+        for (index, time) in times.iter().enumerate() {
+            let previous = current.last().and_then(|x| times[*x]);
+            match (previous, time) {
+                (Some(previous), Some(time)) if (time - previous).abs() <= gap => current.push(index),
+                _ => {
+                    if current.len() > 1 {
+                        bursts.push(current);
+                    }
+                    current = vec![index];
+                }
+            }
+        }
+        if current.len() > 1 {
+            bursts.push(current);
+        }
+
+        bursts
+    }
+
+    // the images hidden behind the pick while collapsed
+    pub fn hidden(self: &Self) -> impl Iterator<Item = &String> {
+        let pick = &self.pick;
+        let expanded = self.expanded;
+        self.paths.iter().filter(move |x| !expanded && *x != pick)
+    }
+}
@@ -0,0 +1,492 @@
+use serde::{ Serialize, Deserialize };
+use std::{ cmp::Ordering, collections::{ HashMap, HashSet } };
+
+use crate::util::fuzzy;
+
+pub type TagId = usize;
+
+pub const TAG_PATH_SEPARATOR: char = '/';
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TagColor {
+    pub r: u8,
+    pub g: u8,
+    pub b: u8,
+}
+
+// picked to stay readable against the dark background and distinct from each other
+pub const TAG_PALETTE: [TagColor; 12] = [
+    TagColor { r: 0xE0, g: 0x6C, b: 0x75 },
+    TagColor { r: 0x98, g: 0xC3, b: 0x79 },
+    TagColor { r: 0x61, g: 0xAF, b: 0xEF },
+    TagColor { r: 0xE5, g: 0xC0, b: 0x7B },
+    TagColor { r: 0xC6, g: 0x78, b: 0xDD },
+    TagColor { r: 0x56, g: 0xB6, b: 0xC2 },
+    TagColor { r: 0xD1, g: 0x9A, b: 0x66 },
+    TagColor { r: 0xBE, g: 0x50, b: 0x46 },
+    TagColor { r: 0x7E, g: 0xC6, b: 0x99 },
+    TagColor { r: 0x9B, g: 0x8C, b: 0xF2 },
+    TagColor { r: 0xF2, g: 0x8F, b: 0xB1 },
+    TagColor { r: 0xAB, g: 0xB2, b: 0xBF },
+];
+
+impl TagColor {
+    pub fn from_hex(hex: &str) -> Option<TagColor> {
+        let hex = hex.trim().trim_start_matches('#');
+        if hex.len() != 6 {
+            return None;
+        }
+
+        let channel = |i: usize| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok();
+        Some(TagColor { r: channel(0)?, g: channel(2)?, b: channel(4)? })
+    }
+
+    pub fn to_hex(self: &Self) -> String {
+        format!("#{:02X}{:02X}{:02X}", self.r, self.g, self.b)
+    }
+
+    pub fn is_light(self: &Self) -> bool {
+        (299 * self.r as u32 + 587 * self.g as u32 + 114 * self.b as u32) / 1000 > 150
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum TagOrder {
+    Manual,
+    Key,
+    Label,
+    Usage,
+}
+
+impl TagOrder {
+    const ORDERS: [TagOrder; 4] = [TagOrder::Manual, TagOrder::Key, TagOrder::Label, TagOrder::Usage];
+
+    pub fn next(self: &mut Self) {
+        if let Some(current_order) = TagOrder::ORDERS.iter().position(|x| x == self) {
+            *self = TagOrder::ORDERS[(current_order + 1) % TagOrder::ORDERS.len()];
+        }
+    }
+
+    pub fn label(self: &Self) -> &'static str {
+        match self {
+            TagOrder::Manual => "manual",
+            TagOrder::Key => "by key",
+            TagOrder::Label => "by label",
+            TagOrder::Usage => "by usage"
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<TagOrder> {
+        match name.trim().to_lowercase().as_str() {
+            "manual" => Some(TagOrder::Manual),
+            "key" => Some(TagOrder::Key),
+            "label" => Some(TagOrder::Label),
+            "usage" => Some(TagOrder::Usage),
+            _ => None
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Tag {
+    pub id: TagId,
+    pub name: String,
+    pub key: Option<char>,
+    pub parent: Option<TagId>,
+    pub color: TagColor,
+}
+
+#[derive(Debug)]
+pub struct TagRegistry {
+    pub order: TagOrder,
+    tags: Vec::<Tag>,
+    next_id: TagId,
+    usage: HashMap<TagId, usize>,
+}
+
+fn split_path(path: &str) -> Vec::<&str> {
+    path.split(TAG_PATH_SEPARATOR)
+        .map(|x| x.trim())
+        .filter(|x| !x.is_empty())
+        .collect()
+}
+
+impl TagRegistry {
+    pub fn new() -> TagRegistry {
+        TagRegistry {
+            order: TagOrder::Manual,
+            tags: Vec::<Tag>::new(),
+            next_id: 0,
+            usage: HashMap::<TagId, usize>::new()
+        }
+    }
+
+    pub fn create(self: &mut Self, name: String, key: Option<char>, parent: Option<TagId>) -> TagId {
+        let id = self.next_id;
+        self.next_id = self.next_id + 1;
+        self.tags.push(Tag {
+            id: id,
+            name: name,
+            key: key,
+            parent: parent,
+            color: TAG_PALETTE[id % TAG_PALETTE.len()]
+        });
+
+        id
+    }
+
+    // "animals/cats" creates (or reuses) "animals" and puts "cats" beneath it,
+    // the key is only bound to the last tag in the path
+    pub fn create_path(self: &mut Self, path: &str, key: Option<char>) -> Option<TagId> {
+        let mut parent = None;
+        for name in split_path(path) {
+            let tag = match self.child(parent, name) {
+                Some(existing) => existing,
+                None => self.create(name.to_string(), None, parent)
+            };
+            parent = Some(tag);
+        }
+
+        if let (Some(id), Some(_)) = (parent, key) {
+            if self.get(id).and_then(|x| x.key).is_none() {
+                let _ = self.rebind(id, key);
+            }
+        }
+
+        parent
+    }
+
+    // A path in the new name moves the tag beneath that parent. Nothing is created
+    // until the rename is known to succeed, so a rejected one leaves no empty parents.
+    pub fn rename(self: &mut Self, id: TagId, path: String) -> Result<(), String> {
+        let mut segments = split_path(&path);
+        let name = match segments.pop() {
+            Some(name) => name.to_string(),
+            None => return Err(String::from("Tag name can't be empty"))
+        };
+
+        // follows the parent path as far as it already exists
+        let mut existing = None;
+        let mut missing = segments.len();
+        for (i, segment) in segments.iter().enumerate() {
+            match self.child(existing, segment) {
+                Some(tag) => existing = Some(tag),
+                None => {
+                    missing = i;
+                    break;
+                }
+            }
+        }
+
+        // any missing parents would be created beneath the existing part of the path
+        if let Some(existing) = existing {
+            if existing == id || self.is_descendant_of(existing, id) {
+                return Err(format!("{} can't be moved beneath itself", self.path(id)));
+            }
+        }
+        if missing == segments.len() {
+            if let Some(other) = self.child(existing, &name) {
+                if other != id {
+                    return Err(format!("A tag named {} already exists", path));
+                }
+            }
+        }
+        if self.get(id).is_none() {
+            return Err(String::from("Tag no longer exists"));
+        }
+
+        let parent = match segments.is_empty() {
+            true => None,
+            false => self.create_path(&segments.join(&TAG_PATH_SEPARATOR.to_string()), None)
+        };
+        if let Some(tag) = self.tags.iter_mut().find(|x| x.id == id) {
+            tag.name = name;
+            tag.parent = parent;
+        }
+        Ok(())
+    }
+
+    pub fn rebind(self: &mut Self, id: TagId, key: Option<char>) -> Result<(), String> {
+        if let Some(key) = key {
+            if let Some(existing) = self.by_key(key) {
+                if existing.id != id {
+                    return Err(format!("{} is already bound to {}", key, self.path(existing.id)));
+                }
+            }
+        }
+
+        match self.tags.iter_mut().find(|x| x.id == id) {
+            Some(tag) => {
+                tag.key = key;
+                Ok(())
+            }
+            None => Err(String::from("Tag no longer exists"))
+        }
+    }
+
+    pub fn set_color(self: &mut Self, id: TagId, color: TagColor) {
+        if let Some(tag) = self.tags.iter_mut().find(|x| x.id == id) {
+            tag.color = color;
+        }
+    }
+
+    // steps through the palette, custom colors start over at the first entry
+    pub fn cycle_color(self: &mut Self, id: TagId) {
+        if let Some(tag) = self.tags.iter_mut().find(|x| x.id == id) {
+            let next = match TAG_PALETTE.iter().position(|x| *x == tag.color) {
+                Some(index) => (index + 1) % TAG_PALETTE.len(),
+                None => 0
+            };
+            tag.color = TAG_PALETTE[next];
+        }
+    }
+
+    pub fn color(self: &Self, id: TagId) -> TagColor {
+        match self.get(id) {
+            Some(tag) => tag.color,
+            None => TAG_PALETTE[TAG_PALETTE.len() - 1]
+        }
+    }
+
+    // children of a removed tag move up to the removed tag's parent
+    pub fn remove(self: &mut Self, id: TagId) -> Option<Tag> {
+        let index = self.tags.iter().position(|x| x.id == id)?;
+        let removed = self.tags.remove(index);
+        for tag in self.tags.iter_mut().filter(|x| x.parent == Some(id)) {
+            tag.parent = removed.parent;
+        }
+
+        Some(removed)
+    }
+
+    // puts back a tag removed by undo, keeping its id so images can refer to it again
+    pub fn insert(self: &mut Self, tag: Tag) {
+        if self.get(tag.id).is_some() {
+            return;
+        }
+
+        self.next_id = self.next_id.max(tag.id + 1);
+        self.tags.push(tag);
+    }
+
+    // leaves the tag where it is rather than put it beneath itself
+    pub fn set_parent(self: &mut Self, id: TagId, parent: Option<TagId>) {
+        if parent.map(|x| x == id || self.is_descendant_of(x, id)).unwrap_or(false) {
+            return;
+        }
+        if let Some(tag) = self.tags.iter_mut().find(|x| x.id == id) {
+            tag.parent = parent;
+        }
+    }
+
+    pub fn get(self: &Self, id: TagId) -> Option<&Tag> {
+        self.tags.iter().find(|x| x.id == id)
+    }
+
+    pub fn by_key(self: &Self, key: char) -> Option<&Tag> {
+        self.tags.iter().find(|x| x.key == Some(key))
+    }
+
+    pub fn child(self: &Self, parent: Option<TagId>, name: &str) -> Option<TagId> {
+        self.tags.iter()
+                 .find(|x| x.parent == parent && x.name == name)
+                 .map(|x| x.id)
+    }
+
+    pub fn find_path(self: &Self, path: &str) -> Option<TagId> {
+        let mut current = None;
+        for name in split_path(path) {
+            current = Some(self.child(current, name)?);
+        }
+
+        current
+    }
+
+    pub fn name(self: &Self, id: TagId) -> String {
+        match self.get(id) {
+            Some(tag) => tag.name.clone(),
+            None => String::from("?")
+        }
+    }
+
+    pub fn path(self: &Self, id: TagId) -> String {
+        let mut names: Vec::<String> = self.ancestors(id).iter().rev().map(|x| self.name(*x)).collect();
+        names.push(self.name(id));
+        names.join(&TAG_PATH_SEPARATOR.to_string())
+    }
+
+    // closest parent first
+    pub fn ancestors(self: &Self, id: TagId) -> Vec::<TagId> {
+        let mut ancestors = Vec::<TagId>::new();
+        let mut current = self.get(id).and_then(|x| x.parent);
+        while let Some(parent) = current {
+            if ancestors.contains(&parent) {
+                break;
+            }
+            ancestors.push(parent);
+            current = self.get(parent).and_then(|x| x.parent);
+        }
+
+        ancestors
+    }
+
+    pub fn is_descendant_of(self: &Self, id: TagId, ancestor: TagId) -> bool {
+        self.ancestors(id).contains(&ancestor)
+    }
+
+    // applying a child tag implies all of its parents
+    pub fn expand(self: &Self, tags: &HashSet<TagId>) -> HashSet<TagId> {
+        let mut expanded = tags.clone();
+        for tag in tags.iter() {
+            expanded.extend(self.ancestors(*tag));
+        }
+
+        expanded
+    }
+
+    pub fn implies(self: &Self, tags: &HashSet<TagId>, tag: TagId) -> bool {
+        tags.iter().any(|x| *x == tag || self.is_descendant_of(*x, tag))
+    }
+
+    pub fn set_usage(self: &mut Self, usage: HashMap<TagId, usize>) {
+        self.usage = usage;
+    }
+
+    // depth first, siblings sorted by the current order
+    pub fn tree(self: &Self) -> Vec::<(usize, &Tag)> {
+        self.tree_by(self.order)
+    }
+
+    pub fn tree_by(self: &Self, order: TagOrder) -> Vec::<(usize, &Tag)> {
+        let mut tree = Vec::<(usize, &Tag)>::new();
+        self.push_children(None, 0, order, &mut tree);
+        tree
+    }
+
+    fn push_children<'a>(self: &'a Self, parent: Option<TagId>, depth: usize, order: TagOrder,
+                         tree: &mut Vec::<(usize, &'a Tag)>) {
+        let mut children: Vec::<&Tag> = self.tags.iter().filter(|x| x.parent == parent).collect();
+        // sort_by is stable, so ties keep their manual order
+        children.sort_by(|a, b| self.compare(a, b, order));
+
+        for tag in children {
+            tree.push((depth, tag));
+            self.push_children(Some(tag.id), depth + 1, order, tree);
+        }
+    }
+
+    fn compare(self: &Self, a: &Tag, b: &Tag, order: TagOrder) -> Ordering {
+        match order {
+            TagOrder::Manual => Ordering::Equal,
+            // unbound tags go last
+            TagOrder::Key => match (a.key, b.key) {
+                (Some(x), Some(y)) => x.to_ascii_lowercase().cmp(&y.to_ascii_lowercase()).then(x.cmp(&y)),
+                (Some(_), None) => Ordering::Less,
+                (None, Some(_)) => Ordering::Greater,
+                (None, None) => Ordering::Equal
+            },
+            TagOrder::Label => a.name.to_lowercase().cmp(&b.name.to_lowercase()),
+            TagOrder::Usage => {
+                let usage = |tag: &Tag| *self.usage.get(&tag.id).unwrap_or(&0);
+                usage(b).cmp(&usage(a))
+            }
+        }
+    }
+
+    // Moves a tag up or down among its siblings. The order the tags are currently
+    // shown in becomes the manual order first, so moving never jumps unexpectedly.
+    // Returns false when the tag is already first or last.
+    pub fn move_tag(self: &mut Self, id: TagId, offset: isize) -> bool {
+        let shown: Vec::<TagId> = self.tree().iter().map(|x| x.1.id).collect();
+        self.tags.sort_by_key(|x| shown.iter().position(|y| *y == x.id));
+        self.order = TagOrder::Manual;
+
+        let parent = match self.get(id) {
+            Some(tag) => tag.parent,
+            None => return false
+        };
+        let siblings: Vec::<usize> = self.tags.iter()
+                                              .enumerate()
+                                              .filter(|(_, x)| x.parent == parent)
+                                              .map(|(i, _)| i)
+                                              .collect();
+        if let Some(position) = siblings.iter().position(|i| self.tags[*i].id == id) {
+            let target = position as isize + offset;
+            if target >= 0 && (target as usize) < siblings.len() {
+                self.tags.swap(siblings[position], siblings[target as usize]);
+                return true;
+            }
+        }
+
+        false
+    }
+
+    pub fn index_of(self: &Self, id: TagId) -> Option<usize> {
+        self.tree().iter().position(|x| x.1.id == id)
+    }
+
+    pub fn iter(self: &Self) -> std::slice::Iter<'_, Tag> {
+        self.tags.iter()
+    }
+
+    // indexes follow the order of `tree`, which is how the side panel lists tags
+    pub fn id_at(self: &Self, index: usize) -> Option<TagId> {
+        self.tree().get(index).map(|x| x.1.id)
+    }
+
+    pub fn len(self: &Self) -> usize {
+        self.tags.len()
+    }
+
+    pub fn is_empty(self: &Self) -> bool {
+        self.tags.is_empty()
+    }
+
+    // best matches first, ties broken by path so results don't jump around while typing
+    pub fn search(self: &Self, query: &str) -> Vec::<(String, &Tag)> {
+        let mut matches: Vec::<(i32, String, &Tag)> = self.tags.iter()
+                                                          .filter_map(|x| {
+                                                              let path = self.path(x.id);
+                                                              fuzzy::score(query, &path).map(|s| (s, path, x))
+                                                          })
+                                                          .collect();
+        matches.sort_by(|a, b| b.0.cmp(&a.0).then_with(|| a.1.cmp(&b.1)));
+        matches.into_iter().map(|(_, path, x)| (path, x)).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rename_moves_beneath_a_new_parent() {
+        let mut tags = TagRegistry::new();
+        let cats = tags.create_path("cats", None).unwrap();
+        assert!(tags.rename(cats, String::from("animals/felines")).is_ok());
+        assert_eq!(tags.path(cats), "animals/felines");
+        assert_eq!(tags.len(), 2);
+    }
+
+    #[test]
+    fn rejected_rename_creates_nothing() {
+        let mut tags = TagRegistry::new();
+        let cats = tags.create_path("cats", None).unwrap();
+        tags.create_path("pets/dogs", None);
+        assert!(tags.rename(cats, String::from("pets/dogs")).is_err());
+        assert_eq!(tags.len(), 3);
+        assert_eq!(tags.path(cats), "cats");
+    }
+
+    #[test]
+    fn rename_beneath_itself_is_rejected() {
+        let mut tags = TagRegistry::new();
+        let animals = tags.create_path("animals", None).unwrap();
+        let cats = tags.create_path("animals/cats", None).unwrap();
+        assert!(tags.rename(animals, String::from("animals/new/x")).is_err());
+        assert!(tags.rename(animals, String::from("animals/cats/x")).is_err());
+        assert_eq!(tags.len(), 2);
+        assert_eq!(tags.path(cats), "animals/cats");
+        assert_eq!(tags.tree().len(), 2);
+    }
+}
@@ -3,20 +3,33 @@ use iced_native::{ text_input, TextInput };
 use std::{ cell::RefCell };
 
 use crate::states::Message;
+use crate::models::TagId;
 use crate::style;
 
+#[derive(Debug, Clone, Copy)]
+pub enum TagInputTarget {
+    Create(char),
+    Rename(TagId),
+    Filter,
+}
+
 #[derive(Debug)]
 pub struct TagInputState {
     pub tag_input_value: RefCell<String>,
-    pub tag: char
+    pub target: TagInputTarget
 }
 
 impl TagInputState {
     pub fn view<'a>(self: &Self, scroll: &'a mut scrollable::State,
                     text_input_state: &'a mut text_input::State) -> Element<'a, Message> {
+        let placeholder = match self.target {
+            TagInputTarget::Create(_) => "Enter Tag Name",
+            TagInputTarget::Rename(_) => "Enter New Tag Name",
+            TagInputTarget::Filter => "Enter Filter, like cats AND NOT outdoor"
+        };
         let scrollable = Scrollable::new(scroll)
                         .align_items(Align::Start)
-                        .push(TextInput::new(text_input_state, "Enter Tag Name", 
+                        .push(TextInput::new(text_input_state, placeholder, 
                                              &self.tag_input_value.borrow(), Message::TextInputChanged)
                                         .on_submit(Message::TextInputSubmitted)
                                         .padding(10)
@@ -0,0 +1,68 @@
+use iced::{ Scrollable, scrollable, Length, Column, Container, Element, Align, Text };
+use iced_native::{ text_input, TextInput };
+use std::{ cell::RefCell, rc::Rc };
+
+use crate::states::Message;
+use crate::models::{ TagId, TagRegistry };
+use crate::style;
+
+const MAX_RESULTS: usize = 10;
+
+#[derive(Debug)]
+pub struct TagPickerState {
+    pub query: RefCell<String>,
+    pub tags: Rc::<RefCell::<TagRegistry>>,
+}
+
+impl TagPickerState {
+    pub fn view<'a>(self: &Self, scroll: &'a mut scrollable::State,
+                    text_input_state: &'a mut text_input::State) -> Element<'a, Message> {
+        let tags = self.tags.borrow();
+        let matches = tags.search(&self.query.borrow());
+
+        let results = matches.iter()
+                             .take(MAX_RESULTS)
+                             .enumerate()
+                             .fold(Column::<'_, Message>::new(), |c, (i, (path, tag))| {
+                                 let label = match tag.key {
+                                     Some(key) => format!("{} - {}", key, path),
+                                     None => format!("    {}", path)
+                                 };
+                                 c.push(Container::new(Text::new(label))
+                                         .width(Length::Fill)
+                                         .style(style::ImageQueueItem { is_selected: i == 0 }))
+                             });
+
+        let scrollable = Scrollable::new(scroll)
+                        .align_items(Align::Start)
+                        .push(TextInput::new(text_input_state, "Search Tags (Enter on no match creates it)",
+                                             &self.query.borrow(), Message::TagPickerChanged)
+                                        .on_submit(Message::TagPickerSubmitted)
+                                        .padding(10)
+                                        .size(20))
+                        .push(results);
+
+        Container::new(scrollable)
+            .width(Length::Fill)
+            .height(Length::Fill)
+            .style(style::Pane { })
+            .center_x()
+            .into()
+    }
+
+    pub fn best_match(self: &Self) -> Option<TagId> {
+        let query = self.query.borrow();
+        if query.trim().is_empty() {
+            return None;
+        }
+
+        self.tags.borrow()
+                 .search(&query)
+                 .first()
+                 .map(|x| x.1.id)
+    }
+
+    pub fn set(self: &Self, value: String) {
+        *self.query.borrow_mut() = value;
+    }
+}
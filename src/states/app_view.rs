@@ -6,6 +6,9 @@ pub enum AppView {
     ImageQueue(ImageQueueState),
     ImageDisplay(ImageDisplayState),
     TagInput(TagInputState),
+    TagPicker(TagPickerState),
+    Help(HelpState),
+    CommandPalette(CommandPaletteState),
 }
 
 impl AppView {
@@ -23,6 +26,13 @@ impl AppView {
         }
     }
 
+    pub fn image_display(self: &Self) -> &ImageDisplayState {
+        match self {
+            AppView::ImageDisplay(x) => x,
+            _ => panic!("Incorrect variant requested")
+        }
+    }
+
     pub fn tag_input(self: &Self) -> &TagInputState {
         match self {
             AppView::TagInput(x) => x,
@@ -30,6 +40,27 @@ impl AppView {
         }
     }
 
+    pub fn tag_picker(self: &Self) -> &TagPickerState {
+        match self {
+            AppView::TagPicker(x) => x,
+            _ => panic!("Incorrect variant requested")
+        }
+    }
+
+    pub fn command_palette(self: &Self) -> &CommandPaletteState {
+        match self {
+            AppView::CommandPalette(x) => x,
+            _ => panic!("Incorrect variant requested")
+        }
+    }
+
+    pub fn side_panel_mut(self: &mut Self) -> &mut SidePanelState {
+        match self {
+            AppView::SidePanel(x) => x,
+            _ => panic!("Incorrect variant requested")
+        }
+    }
+
     pub fn image_display_mut(self: &mut Self) -> &mut ImageDisplayState {
         match self {
             AppView::ImageDisplay(x) => x,
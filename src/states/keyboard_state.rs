@@ -1,6 +1,46 @@
+use crate::models::TagId;
+
 #[derive(Debug)]
 pub enum KeyboardState {
     Tagging,
+    SidePanel,
+    Rebinding(TagId),
     None
 }
 
+// a count, the first key of a sequence like gg, or a tag jump or group tag waiting for its tag key
+#[derive(Debug, Default)]
+pub struct PendingKeys {
+    pub count: Option<usize>,
+    pub prefix: Option<char>,
+    pub tag_jump: Option<bool>,
+    pub group_tag: bool,
+}
+
+impl PendingKeys {
+    pub fn push_digit(self: &mut Self, digit: u32) {
+        let count = self.count.unwrap_or(0);
+        self.count = Some(count.saturating_mul(10).saturating_add(digit as usize));
+    }
+
+    pub fn clear(self: &mut Self) {
+        *self = PendingKeys::default();
+    }
+
+    pub fn label(self: &Self) -> String {
+        let mut label = self.count.map(|x| x.to_string()).unwrap_or_default();
+        if let Some(prefix) = self.prefix {
+            label.push(prefix);
+        }
+        match self.tag_jump {
+            Some(true) => label.push_str(" next tagged..."),
+            Some(false) => label.push_str(" previous tagged..."),
+            None => ()
+        }
+        if self.group_tag {
+            label.push_str(" tag group...");
+        }
+
+        label
+    }
+}
@@ -1,12 +1,20 @@
 use iced::{ pane_grid };
 use iced_native::{ Event };
 use crate::states::organize_mode::OrganizeMode;
+use crate::util::perceptual_hash::ImageHashes;
 
 #[derive(Debug, Clone)]
 pub enum Message {
     EventOccurred(Event),
     TextInputChanged(String),
     TextInputSubmitted,
+    TagPickerChanged(String),
+    TagPickerSubmitted,
+    CommandPaletteChanged(String),
+    CommandPaletteSubmitted,
     Resized(pane_grid::ResizeEvent),
-    SelectedOrganizeMode(OrganizeMode)
+    SelectedOrganizeMode(OrganizeMode),
+    IntegrityChecked(Vec::<(String, Option<String>)>),
+    ImagesHashed(Vec::<(String, Result<ImageHashes, String>)>),
+    AnimationTick
 }
@@ -16,4 +16,13 @@ impl OrganizeMode {
             *self = OrganizeMode::MODES[current_mode];
         }
     }
+
+    pub fn from_name(name: &str) -> Option<OrganizeMode> {
+        match name.trim().to_lowercase().as_str() {
+            "copy" => Some(OrganizeMode::Copy),
+            "move" => Some(OrganizeMode::Move),
+            "link" => Some(OrganizeMode::Link),
+            _ => None
+        }
+    }
 }
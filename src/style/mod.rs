@@ -1,4 +1,5 @@
-use iced::{container, Background, Color};
+use iced::{container, progress_bar, Background, Color};
+use crate::models::TagColor;
 
 const BACKGROUND: Color = Color::from_rgb(
     0x1F as f32 / 255.0,
@@ -77,3 +78,40 @@ impl container::StyleSheet for Pane {
         }
     }
 }
+
+pub fn tag_color(color: TagColor) -> Color {
+    Color::from_rgb(
+        color.r as f32 / 255.0,
+        color.g as f32 / 255.0,
+        color.b as f32 / 255.0,
+    )
+}
+
+pub struct TagBadge {
+    pub color: TagColor
+}
+impl container::StyleSheet for TagBadge {
+    fn style(&self) -> container::Style {
+        let text_color = if self.color.is_light() { BACKGROUND } else { Color::WHITE };
+        container::Style {
+            text_color: Some(text_color),
+            background: Some(Background::Color(tag_color(self.color))),
+            border_radius: 4,
+            ..Default::default()
+        }
+    }
+}
+
+pub struct Progress { }
+impl progress_bar::StyleSheet for Progress {
+    fn style(&self) -> progress_bar::Style {
+        progress_bar::Style {
+            background: Background::Color(Color {
+                a: 0.3,
+                ..Color::BLACK
+            }),
+            bar: Background::Color(SELECTED),
+            border_radius: 4,
+        }
+    }
+}
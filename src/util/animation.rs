@@ -0,0 +1,194 @@
+use image::{ codecs::{ gif::GifDecoder, png::PngDecoder }, imageops, AnimationDecoder, ImageFormat, Rgba, RgbaImage };
+use std::{ fs::{ self, File }, io::BufReader, time::Duration };
+
+use crate::util::companions;
+
+// browsers play GIFs asking for no delay at all this slowly, and so do we
+const DEFAULT_DELAY_MS: u64 = 100;
+const MIN_DELAY_MS: u64 = 20;
+// libwebp won't make a canvas any wider or taller than this
+const MAX_WEBP_SIZE: u32 = 16383;
+// every frame is kept as a full canvas, so long or large animations stop here
+const MAX_ANIMATION_BYTES: u64 = 1 << 30;
+
+pub struct Frame {
+    pub image: RgbaImage,
+    pub delay: Duration,
+}
+
+fn delay(milliseconds: u64) -> Duration {
+    match milliseconds {
+        x if x < MIN_DELAY_MS => Duration::from_millis(DEFAULT_DELAY_MS),
+        x => Duration::from_millis(x)
+    }
+}
+
+fn collect<'a>(decoder: impl AnimationDecoder<'a>) -> Result<Vec::<Frame>, String> {
+    decoder.into_frames()
+           .map(|x| x.map(|frame| {
+               let (numerator, denominator) = frame.delay().numer_denom_ms();
+               Frame {
+                   delay: delay((numerator / denominator.max(1)) as u64),
+                   image: frame.into_buffer()
+               }
+           }))
+           .collect::<Result<Vec::<Frame>, _>>()
+           .map_err(|e| e.to_string())
+}
+
+fn u24(bytes: &[u8]) -> u32 {
+    bytes[0] as u32 | (bytes[1] as u32) << 8 | (bytes[2] as u32) << 16
+}
+
+// the chunks of a RIFF file as (fourcc, payload)
+pub fn riff_chunks(data: &[u8]) -> Vec::<(&[u8], &[u8])> {
+    let mut chunks = Vec::<(&[u8], &[u8])>::new();
+    let mut position = 0;
+    while position + 8 <= data.len() {
+        let size = u32::from_le_bytes([data[position + 4], data[position + 5],
+                                       data[position + 6], data[position + 7]]) as usize;
+        let end = (position + 8).saturating_add(size).min(data.len());
+        chunks.push((&data[position..position + 4], &data[position + 8..end]));
+        // chunks are padded to an even length
+        position = end + size % 2;
+    }
+    chunks
+}
+
+// The image crate only decodes still WebPs, so each frame's lossy bitstream is
+// wrapped up as one and the frames are put together here. Frame transparency is
+// lost and lossless frames can't be shown.
+fn webp_frames(path: &str) -> Result<Option<Vec::<Frame>>, String> {
+    let data = fs::read(path).map_err(|e| e.to_string())?;
+    if data.get(0..4) != Some(&b"RIFF"[..]) || data.get(8..12) != Some(&b"WEBP"[..]) {
+        return Err(String::from("not a WebP file"));
+    }
+
+    let chunks = riff_chunks(&data[12..]);
+    // bit 1 of the VP8X flags marks an animation
+    let (width, height) = match chunks.iter().find(|(name, payload)| *name == b"VP8X" && payload.len() >= 10) {
+        Some((_, payload)) if payload[0] & 0b10 != 0 => (u24(&payload[4..7]) + 1, u24(&payload[7..10]) + 1),
+        _ => return Ok(None)
+    };
+    // the sizes come straight from the file, so they're checked before anything is allocated for them
+    if width > MAX_WEBP_SIZE || height > MAX_WEBP_SIZE {
+        return Err(format!("animation canvas of {}x{} is larger than WebP allows", width, height));
+    }
+    let frame_count = chunks.iter().filter(|(name, payload)| *name == b"ANMF" && payload.len() >= 16).count() as u64;
+    if frame_count == 0 {
+        return Err(String::from("animation has no frames"));
+    }
+    if frame_count * width as u64 * height as u64 * 4 > MAX_ANIMATION_BYTES {
+        return Err(format!("animation of {} {}x{} frames is too large to play", frame_count, width, height));
+    }
+
+    let mut canvas = RgbaImage::new(width, height);
+    let mut frames = Vec::<Frame>::new();
+    let mut dispose: Option<(u32, u32, u32, u32)> = None;
+    for (_, payload) in chunks.iter().filter(|(name, payload)| *name == b"ANMF" && payload.len() >= 16) {
+        let (x, y) = (u24(&payload[0..3]) * 2, u24(&payload[3..6]) * 2);
+        let (frame_width, frame_height) = (u24(&payload[6..9]) + 1, u24(&payload[9..12]) + 1);
+        let flags = payload[15];
+        if x + frame_width > width || y + frame_height > height {
+            return Err(format!("animation frame at {},{} of {}x{} is outside the {}x{} canvas",
+                               x, y, frame_width, frame_height, width, height));
+        }
+
+        // the previous frame asked for its area to be cleared once it had been shown
+        if let Some((x, y, width, height)) = dispose.take() {
+            let cleared = RgbaImage::from_pixel(width, height, Rgba([0, 0, 0, 0]));
+            imageops::replace(&mut canvas, &cleared, x, y);
+        }
+
+        let bitstream = riff_chunks(&payload[16..]).into_iter().find(|(name, _)| *name == b"VP8 " || *name == b"VP8L");
+        let bitstream = match bitstream {
+            Some((name, _)) if name == b"VP8L" => return Err(String::from("lossless animated WebP frames aren't supported")),
+            Some((_, bitstream)) => bitstream,
+            None => return Err(String::from("animation frame has no image data"))
+        };
+        let mut still = Vec::<u8>::with_capacity(bitstream.len() + 20);
+        still.extend_from_slice(b"RIFF");
+        still.extend_from_slice(&(12 + bitstream.len() as u32 + bitstream.len() as u32 % 2).to_le_bytes());
+        still.extend_from_slice(b"WEBPVP8 ");
+        still.extend_from_slice(&(bitstream.len() as u32).to_le_bytes());
+        still.extend_from_slice(bitstream);
+        if bitstream.len() % 2 == 1 {
+            still.push(0);
+        }
+        let image = image::load_from_memory_with_format(&still, ImageFormat::WebP).map_err(|e| e.to_string())?
+                                                                                   .to_rgba8();
+
+        // bit 1 set means the frame replaces what's under it rather than being blended on top
+        match flags & 0b10 {
+            0 => imageops::overlay(&mut canvas, &image, x, y),
+            _ => imageops::replace(&mut canvas, &image, x, y)
+        }
+        if flags & 0b1 == 1 {
+            dispose = Some((x, y, frame_width, frame_height));
+        }
+        frames.push(Frame { image: canvas.clone(), delay: delay(u24(&payload[12..15]) as u64) });
+    }
+
+    match frames.is_empty() {
+        true => Ok(None),
+        false => Ok(Some(frames))
+    }
+}
+
+// Every frame of an animated GIF, APNG or WebP drawn onto the full canvas,
+// or None when the file isn't animated.
+pub fn frames(path: &str) -> Result<Option<Vec::<Frame>>, String> {
+    let open = || File::open(path).map(BufReader::new).map_err(|e| e.to_string());
+    let frames = match companions::extension(path).as_str() {
+        "gif" => collect(GifDecoder::new(open()?).map_err(|e| e.to_string())?)?,
+        "png" | "apng" => {
+            let decoder = PngDecoder::new(open()?).map_err(|e| e.to_string())?;
+            match decoder.is_apng() {
+                true => collect(decoder.apng())?,
+                false => return Ok(None)
+            }
+        }
+        "webp" => return webp_frames(path),
+        _ => return Ok(None)
+    };
+
+    match frames.len() {
+        0 | 1 => Ok(None),
+        _ => Ok(Some(frames))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // a RIFF file with only a VP8X chunk, which is all it takes to claim any canvas size
+    fn write_vp8x(name: &str, flags: u8, width: u32, height: u32) -> String {
+        let mut data = Vec::<u8>::new();
+        data.extend_from_slice(b"RIFF");
+        data.extend_from_slice(&22u32.to_le_bytes());
+        data.extend_from_slice(b"WEBPVP8X");
+        data.extend_from_slice(&10u32.to_le_bytes());
+        data.extend_from_slice(&[flags, 0, 0, 0]);
+        data.extend_from_slice(&(width - 1).to_le_bytes()[..3]);
+        data.extend_from_slice(&(height - 1).to_le_bytes()[..3]);
+
+        let path = std::env::temp_dir().join(format!("image_organizer_{}_{}.webp", name, std::process::id()));
+        fs::write(&path, data).unwrap();
+        path.to_string_lossy().to_string()
+    }
+
+    #[test]
+    fn huge_canvas_is_rejected_before_allocating() {
+        let path = write_vp8x("huge", 0b10, 1 << 24, 1 << 24);
+        assert!(webp_frames(&path).is_err());
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn webp_without_animation_flag_is_still() {
+        let path = write_vp8x("still", 0, 1 << 24, 1 << 24);
+        assert!(webp_frames(&path).unwrap().is_none());
+        fs::remove_file(path).unwrap();
+    }
+}
@@ -0,0 +1,30 @@
+use iced::futures::channel::oneshot;
+use std::{ any::Any, panic::{ self, AssertUnwindSafe }, thread };
+
+fn panic_message(panic: Box<dyn Any + Send>) -> String {
+    match (panic.downcast_ref::<&str>(), panic.downcast_ref::<String>()) {
+        (Some(message), _) => message.to_string(),
+        (_, Some(message)) => message.clone(),
+        _ => String::from("unknown error")
+    }
+}
+
+// a decoder panicking on a damaged file becomes an error for that file rather than ending the work
+pub fn catch_panic<T>(work: impl FnOnce() -> Result<T, String>) -> Result<T, String> {
+    panic::catch_unwind(AssertUnwindSafe(work)).unwrap_or_else(|x| Err(format!("decoder crashed: {}", panic_message(x))))
+}
+
+// Runs slow work like decoding images on its own thread, so waiting for it doesn't
+// hold up the executor the rest of the app runs on.
+pub async fn run<T: Send + 'static>(work: impl FnOnce() -> T + Send + 'static) -> Result<T, String> {
+    let (sender, receiver) = oneshot::channel();
+    thread::spawn(move || {
+        let _ = sender.send(panic::catch_unwind(AssertUnwindSafe(work)));
+    });
+
+    match receiver.await {
+        Ok(Ok(result)) => Ok(result),
+        Ok(Err(panic)) => Err(format!("background work crashed: {}", panic_message(panic))),
+        Err(_) => Err(String::from("background work stopped without a result"))
+    }
+}
@@ -0,0 +1,12 @@
+use iced::{ Length, Text, Container };
+
+use crate::models::TagColor;
+use crate::style;
+
+pub fn tag_badge<'a, T>(label: String, color: TagColor, size: u16) -> Container<'a, T> {
+    Container::new(Text::new(label).size(size))
+        .width(Length::Shrink)
+        .height(Length::Shrink)
+        .padding(2)
+        .style(style::TagBadge { color: color })
+}
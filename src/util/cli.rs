@@ -0,0 +1,54 @@
+use std::path::PathBuf;
+
+const USAGE: &str = "Usage: image_organizer [DIRECTORY] [--preset FILE]... [--global-preset] [--project-preset] [--save-preset FILE] [--quarantine DIRECTORY] [--keymap FILE] [--burst-gap SECONDS]";
+
+#[derive(Debug, Default)]
+pub struct Options {
+    pub working_directory: Option<String>,
+    pub presets: Vec::<PathBuf>,
+    pub use_global_preset: bool,
+    pub use_project_preset: bool,
+    pub save_preset: Option<PathBuf>,
+    pub quarantine: Option<PathBuf>,
+    pub keymap: Option<PathBuf>,
+    pub burst_gap: Option<f64>,
+}
+
+impl Options {
+    pub fn parse(args: &[String]) -> Result<Options, String> {
+        let mut options = Options::default();
+        let mut args = args.iter().skip(1);
+
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--preset" => match args.next() {
+                    Some(path) => options.presets.push(PathBuf::from(path)),
+                    None => return Err(format!("--preset needs a file\n{}", USAGE))
+                },
+                "--save-preset" => match args.next() {
+                    Some(path) => options.save_preset = Some(PathBuf::from(path)),
+                    None => return Err(format!("--save-preset needs a file\n{}", USAGE))
+                },
+                "--quarantine" => match args.next() {
+                    Some(path) => options.quarantine = Some(PathBuf::from(path)),
+                    None => return Err(format!("--quarantine needs a directory\n{}", USAGE))
+                },
+                "--keymap" => match args.next() {
+                    Some(path) => options.keymap = Some(PathBuf::from(path)),
+                    None => return Err(format!("--keymap needs a file\n{}", USAGE))
+                },
+                "--burst-gap" => match args.next().and_then(|x| x.parse::<f64>().ok()) {
+                    Some(gap) if gap >= 0.0 => options.burst_gap = Some(gap),
+                    _ => return Err(format!("--burst-gap needs a number of seconds\n{}", USAGE))
+                },
+                "--global-preset" => options.use_global_preset = true,
+                "--project-preset" => options.use_project_preset = true,
+                "--help" | "-h" => return Err(USAGE.to_string()),
+                _ if arg.starts_with("--") => return Err(format!("Unknown option {}\n{}", arg, USAGE)),
+                _ => options.working_directory = Some(arg.clone())
+            }
+        }
+
+        Ok(options)
+    }
+}
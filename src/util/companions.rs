@@ -0,0 +1,113 @@
+use std::collections::BTreeMap;
+
+// formats a camera writes next to each other for one shot, best to show first
+const DISPLAYED_EXTENSIONS: [&str; 9] = ["jpg", "jpeg", "png", "webp", "heic", "heif", "avif", "tif", "tiff"];
+pub const RAW_EXTENSIONS: [&str; 14] = ["cr2", "cr3", "crw", "nef", "nrw", "arw", "srf", "sr2",
+                                        "dng", "raf", "orf", "rw2", "pef", "srw"];
+// metadata and thumbnails that only make sense alongside the photo
+const SIDECAR_EXTENSIONS: [&str; 6] = ["xmp", "thm", "aae", "pp3", "dop", "on1"];
+
+// where the extension's dot is, a leading dot like .hidden doesn't start one
+fn extension_start(path: &str) -> Option<usize> {
+    let name_start = path.rfind('/').map(|x| x + 1).unwrap_or(0);
+    path.rfind('.').filter(|x| *x > name_start)
+}
+
+pub fn extension(path: &str) -> String {
+    match extension_start(path) {
+        Some(index) => path[index + 1..].to_lowercase(),
+        None => String::from("")
+    }
+}
+
+fn strip_extension(path: &str) -> &str {
+    match extension_start(path) {
+        Some(index) => &path[..index],
+        None => path
+    }
+}
+
+// IMG_1.CR2, IMG_1.JPG, IMG_1.xmp and IMG_1.CR2.xmp all belong to IMG_1, but a
+// sidecar's inner extension only counts when it's an image's, party.1.xmp goes with party.1.jpg
+fn stem(path: &str) -> &str {
+    let stem = strip_extension(path);
+    let inner = extension(stem);
+    let is_image = DISPLAYED_EXTENSIONS.contains(&inner.as_str()) || RAW_EXTENSIONS.contains(&inner.as_str());
+    if SIDECAR_EXTENSIONS.contains(&extension(path).as_str()) && is_image {
+        strip_extension(stem)
+    } else {
+        stem
+    }
+}
+
+fn display_rank(path: &str) -> usize {
+    let extension = extension(path);
+    match DISPLAYED_EXTENSIONS.iter().position(|x| *x == extension) {
+        Some(index) => index,
+        None if RAW_EXTENSIONS.contains(&extension.as_str()) => DISPLAYED_EXTENSIONS.len(),
+        None if SIDECAR_EXTENSIONS.contains(&extension.as_str()) => DISPLAYED_EXTENSIONS.len() + 2,
+        None => DISPLAYED_EXTENSIONS.len() + 1
+    }
+}
+
+// Files sharing a stem become one item: the file to display and its companions,
+// in the order of the primary paths.
+pub fn group(paths: Vec::<String>) -> Vec::<(String, Vec::<String>)> {
+    let mut groups = BTreeMap::<String, Vec::<String>>::new();
+    for path in paths {
+        groups.entry(stem(&path).to_string()).or_insert(Vec::<String>::new()).push(path);
+    }
+
+    let mut items: Vec::<(String, Vec::<String>)> = groups.into_iter()
+                                                          .map(|(_, mut files)| {
+                                                              files.sort_by_key(|x| (display_rank(x), x.clone()));
+                                                              let primary = files.remove(0);
+                                                              (primary, files)
+                                                          })
+                                                          .collect();
+    items.sort_unstable();
+    items
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn grouped(paths: &[&str]) -> Vec::<(String, Vec::<String>)> {
+        group(paths.iter().map(|x| x.to_string()).collect())
+    }
+
+    fn item(primary: &str, companions: &[&str]) -> (String, Vec::<String>) {
+        (primary.to_string(), companions.iter().map(|x| x.to_string()).collect())
+    }
+
+    #[test]
+    fn raw_and_sidecars_go_with_the_jpeg() {
+        let items = grouped(&["./IMG_1.xmp", "./IMG_1.CR2", "./IMG_1.JPG", "./IMG_1.CR2.xmp", "./IMG_1.THM", "./IMG_2.JPG"]);
+        assert_eq!(items, vec![item("./IMG_1.JPG", &["./IMG_1.CR2", "./IMG_1.CR2.xmp", "./IMG_1.THM", "./IMG_1.xmp"]),
+                               item("./IMG_2.JPG", &[])]);
+    }
+
+    #[test]
+    fn raw_is_shown_without_a_jpeg() {
+        let items = grouped(&["./DSC_5.NEF.xmp", "./DSC_5.NEF"]);
+        assert_eq!(items, vec![item("./DSC_5.NEF", &["./DSC_5.NEF.xmp"])]);
+    }
+
+    #[test]
+    fn dots_in_stems_are_kept() {
+        let items = grouped(&["./party.jpg", "./party.1.jpg", "./party.1.xmp", "./DSC.0001.ARW", "./DSC.0001.xmp"]);
+        assert_eq!(items, vec![item("./DSC.0001.ARW", &["./DSC.0001.xmp"]),
+                               item("./party.1.jpg", &["./party.1.xmp"]),
+                               item("./party.jpg", &[])]);
+    }
+
+    #[test]
+    fn different_stems_stay_apart() {
+        let items = grouped(&["./a.jpg", "./a.png", "./b.png", "./.hidden", "./notes.txt"]);
+        assert_eq!(items, vec![item("./.hidden", &[]),
+                               item("./a.jpg", &["./a.png"]),
+                               item("./b.png", &[]),
+                               item("./notes.txt", &[])]);
+    }
+}
@@ -0,0 +1,70 @@
+use std::{ fs, io::{ self, Read }, collections::{ hash_map::DefaultHasher, HashMap }, hash::Hasher };
+
+const CHUNK_SIZE: usize = 64 * 1024;
+
+fn hash_file(path: &str) -> Result<u64, io::Error> {
+    let mut file = fs::File::open(path)?;
+    let mut hasher = DefaultHasher::new();
+    let mut buffer = vec![0; CHUNK_SIZE];
+    loop {
+        let read = file.read(&mut buffer)?;
+        if read == 0 {
+            break;
+        }
+        hasher.write(&buffer[..read]);
+    }
+
+    Ok(hasher.finish())
+}
+
+// Groups the files with identical contents, as indexes into paths in the order given.
+// Only files of the same size get hashed, and files with the same hash are compared
+// in full so a collision can never mark a different photo as a copy.
+// Returns the groups and any files that couldn't be read.
+pub fn find_duplicates(paths: &[String]) -> (Vec::<Vec::<usize>>, Vec::<String>) {
+    let mut problems = Vec::<String>::new();
+    let mut by_size = HashMap::<u64, Vec::<usize>>::new();
+    for (index, path) in paths.iter().enumerate() {
+        match fs::metadata(path) {
+            Ok(metadata) => by_size.entry(metadata.len()).or_insert(Vec::<usize>::new()).push(index),
+            Err(e) => problems.push(format!("Error reading {}: {}", path, e))
+        }
+    }
+
+    let mut groups = Vec::<Vec::<usize>>::new();
+    for same_size in by_size.values().filter(|x| x.len() > 1) {
+        let mut by_hash = HashMap::<u64, Vec::<usize>>::new();
+        for index in same_size.iter() {
+            match hash_file(&paths[*index]) {
+                Ok(hash) => by_hash.entry(hash).or_insert(Vec::<usize>::new()).push(*index),
+                Err(e) => problems.push(format!("Error reading {}: {}", paths[*index], e))
+            }
+        }
+
+        for same_hash in by_hash.values().filter(|x| x.len() > 1) {
+            let mut contents = Vec::<(Vec::<u8>, Vec::<usize>)>::new();
+            for index in same_hash.iter() {
+                let bytes = match fs::read(&paths[*index]) {
+                    Ok(bytes) => bytes,
+                    Err(e) => {
+                        problems.push(format!("Error reading {}: {}", paths[*index], e));
+                        continue;
+                    }
+                };
+                match contents.iter_mut().find(|(x, _)| *x == bytes) {
+                    Some((_, group)) => group.push(*index),
+                    None => contents.push((bytes, vec![*index]))
+                }
+            }
+
+            groups.extend(contents.into_iter().map(|(_, x)| x).filter(|x| x.len() > 1));
+        }
+    }
+
+    for group in groups.iter_mut() {
+        group.sort_unstable();
+    }
+    groups.sort_unstable();
+
+    (groups, problems)
+}
@@ -0,0 +1,224 @@
+use std::{ fs, io::Read };
+
+// EXIF sits near the start of a file, so there's no need to read a whole RAW file for it
+const HEADER_LIMIT: u64 = 1024 * 1024;
+
+const TAG_DATE_TIME: u16 = 0x0132;
+const TAG_EXIF_IFD: u16 = 0x8769;
+const TAG_DATE_TIME_ORIGINAL: u16 = 0x9003;
+const TAG_SUB_SEC_TIME_ORIGINAL: u16 = 0x9291;
+
+#[derive(Debug, Clone, Copy)]
+pub struct Entry {
+    pub tag: u16,
+    pub kind: u16,
+    pub count: u32,
+    position: usize,
+}
+
+// Just enough of a TIFF reader to walk image file directories (IFDs),
+// which is how both EXIF and most RAW formats lay out their metadata.
+pub struct Tiff<'a> {
+    data: &'a [u8],
+    little_endian: bool,
+}
+
+impl<'a> Tiff<'a> {
+    pub fn new(data: &'a [u8]) -> Option<Tiff<'a>> {
+        let little_endian = match data.get(0..4)? {
+            [b'I', b'I', 42, 0] => true,
+            [b'M', b'M', 0, 42] => false,
+            // Olympus and Panasonic RAW files use their own magic numbers after the byte order
+            [b'I', b'I', b'R', _] | [b'I', b'I', 0x55, 0] => true,
+            [b'M', b'M', 0, b'O'] => false,
+            _ => return None
+        };
+
+        Some(Tiff { data: data, little_endian: little_endian })
+    }
+
+    pub fn u16_at(self: &Self, offset: usize) -> Option<u16> {
+        let bytes = self.data.get(offset..offset + 2)?;
+        Some(match self.little_endian {
+            true => u16::from_le_bytes([bytes[0], bytes[1]]),
+            false => u16::from_be_bytes([bytes[0], bytes[1]])
+        })
+    }
+
+    pub fn u32_at(self: &Self, offset: usize) -> Option<u32> {
+        let bytes = self.data.get(offset..offset + 4)?;
+        Some(match self.little_endian {
+            true => u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]),
+            false => u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]])
+        })
+    }
+
+    pub fn first_ifd(self: &Self) -> Option<usize> {
+        self.u32_at(4).map(|x| x as usize)
+    }
+
+    pub fn entries(self: &Self, ifd: usize) -> Vec::<Entry> {
+        let count = self.u16_at(ifd).unwrap_or(0) as usize;
+        (0..count).filter_map(|i| {
+                      let position = ifd + 2 + i * 12;
+                      Some(Entry {
+                          tag: self.u16_at(position)?,
+                          kind: self.u16_at(position + 2)?,
+                          count: self.u32_at(position + 4)?,
+                          position: position + 8
+                      })
+                  })
+                  .collect()
+    }
+
+    pub fn next_ifd(self: &Self, ifd: usize) -> Option<usize> {
+        let count = self.u16_at(ifd)? as usize;
+        match self.u32_at(ifd + 2 + count * 12)? {
+            0 => None,
+            offset => Some(offset as usize)
+        }
+    }
+
+    pub fn find(self: &Self, ifd: usize, tag: u16) -> Option<Entry> {
+        self.entries(ifd).into_iter().find(|x| x.tag == tag)
+    }
+
+    // values that fit in four bytes are stored in the entry itself, bigger ones at an offset
+    fn value_range(self: &Self, entry: &Entry) -> Option<std::ops::Range<usize>> {
+        let size = match entry.kind {
+            1 | 2 | 6 | 7 => 1,
+            3 | 8 => 2,
+            4 | 9 | 11 | 13 => 4,
+            5 | 10 | 12 => 8,
+            _ => return None
+        } * entry.count as usize;
+        let offset = match size {
+            0..=4 => entry.position,
+            _ => self.u32_at(entry.position)? as usize
+        };
+
+        Some(offset..offset.checked_add(size)?)
+    }
+
+    pub fn bytes(self: &Self, entry: &Entry) -> Option<&'a [u8]> {
+        self.data.get(self.value_range(entry)?)
+    }
+
+    pub fn ascii(self: &Self, entry: &Entry) -> Option<String> {
+        let bytes = self.bytes(entry)?;
+        let end = bytes.iter().position(|x| *x == 0).unwrap_or(bytes.len());
+        String::from_utf8(bytes[..end].to_vec()).ok()
+    }
+
+    // SHORT, LONG and IFD values, which is what offsets and sizes come as
+    pub fn numbers(self: &Self, entry: &Entry) -> Vec::<u32> {
+        let offset = match self.value_range(entry) {
+            Some(range) => range.start,
+            None => return Vec::<u32>::new()
+        };
+
+        (0..entry.count as usize).filter_map(|i| match entry.kind {
+                                     3 => self.u16_at(offset + i * 2).map(|x| x as u32),
+                                     4 | 13 => self.u32_at(offset + i * 4),
+                                     _ => None
+                                 })
+                                 .collect()
+    }
+
+    pub fn number(self: &Self, ifd: usize, tag: u16) -> Option<u32> {
+        self.find(ifd, tag).and_then(|x| self.numbers(&x).first().cloned())
+    }
+}
+
+// the marker and contents of each JPEG segment before the image data starts
+pub fn jpeg_segments(bytes: &[u8]) -> Vec::<(u8, &[u8])> {
+    let mut segments = Vec::<(u8, &[u8])>::new();
+    if bytes.get(0..2) != Some(&[0xFF, 0xD8][..]) {
+        return segments;
+    }
+
+    let mut position = 2;
+    while let (Some(0xFF), Some(marker)) = (bytes.get(position), bytes.get(position + 1).cloned()) {
+        match marker {
+            0xFF => {
+                position = position + 1;
+                continue;
+            }
+            0xDA | 0xD9 => break,
+            _ => ()
+        }
+
+        let length = match bytes.get(position + 2..position + 4) {
+            Some(length) => u16::from_be_bytes([length[0], length[1]]) as usize,
+            None => break
+        };
+        match bytes.get(position + 4..position + 2 + length) {
+            Some(contents) => segments.push((marker, contents)),
+            None => break
+        }
+        position = position + 2 + length;
+    }
+    segments
+}
+
+// the TIFF structure holding the EXIF of a JPEG or a TIFF based file
+pub fn exif_data(bytes: &[u8]) -> Option<&[u8]> {
+    if Tiff::new(bytes).is_some() {
+        return Some(bytes);
+    }
+
+    jpeg_segments(bytes).into_iter()
+                        .find(|(marker, contents)| *marker == 0xE1 && contents.starts_with(b"Exif\0\0"))
+                        .map(|(_, contents)| &contents[6..])
+}
+
+pub fn read_header(path: &str) -> Option<Vec::<u8>> {
+    let mut bytes = Vec::<u8>::new();
+    fs::File::open(path).ok()?.take(HEADER_LIMIT).read_to_end(&mut bytes).ok()?;
+    Some(bytes)
+}
+
+// days since 1970-01-01 for a date in the proleptic Gregorian calendar
+fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = (if year >= 0 { year } else { year - 399 }) / 400;
+    let year_of_era = year - era * 400;
+    let day_of_year = (153 * (month + if month > 2 { -3 } else { 9 }) + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146097 + day_of_era - 719468
+}
+
+// "2020:07:14 16:03:27" as seconds, in the camera's local time
+fn parse_date_time(text: &str) -> Option<f64> {
+    let parts: Vec::<i64> = text.trim()
+                                .split(|x| x == ':' || x == ' ')
+                                .map(|x| x.parse::<i64>().ok())
+                                .collect::<Option<Vec::<i64>>>()?;
+    match parts.as_slice() {
+        [year, month, day, hour, minute, second] if *year > 0 => {
+            let days = days_from_civil(*year, *month, *day);
+            Some((days * 86400 + hour * 3600 + minute * 60 + second) as f64)
+        }
+        _ => None
+    }
+}
+
+// when the shot was taken, with the sub second part bursts need when the camera records it
+pub fn capture_time(path: &str) -> Option<f64> {
+    let header = read_header(path)?;
+    let tiff = Tiff::new(exif_data(&header)?)?;
+    let ifd = tiff.first_ifd()?;
+    let exif_ifd = tiff.number(ifd, TAG_EXIF_IFD).map(|x| x as usize);
+
+    let original = exif_ifd.and_then(|x| tiff.find(x, TAG_DATE_TIME_ORIGINAL));
+    let time = match original.or(tiff.find(ifd, TAG_DATE_TIME)) {
+        Some(entry) => parse_date_time(&tiff.ascii(&entry)?)?,
+        None => return None
+    };
+    let fraction = exif_ifd.and_then(|x| tiff.find(x, TAG_SUB_SEC_TIME_ORIGINAL))
+                           .and_then(|x| tiff.ascii(&x))
+                           .and_then(|x| format!("0.{}", x.trim()).parse::<f64>().ok())
+                           .unwrap_or(0.0);
+
+    Some(time + fraction)
+}
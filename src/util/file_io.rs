@@ -1,5 +1,9 @@
 use std::fs;
 use std::path::Path;
+use std::time::UNIX_EPOCH;
+
+// holds presets and sessions inside the folder being organized
+pub const PROJECT_DIRECTORY: &str = ".image_organizer";
 
 pub fn get_directory_list(directory_path:&str) -> Result<Vec<String>, std::io::Error> {
     let mut found_paths: Vec<String> = Vec::new();
@@ -16,3 +20,9 @@ pub fn get_directory_list(directory_path:&str) -> Result<Vec<String>, std::io::E
 
     Ok(found_paths)
 }
+
+// seconds since the epoch the file was last written, a stand-in for when a photo was taken
+pub fn modified_time(path: &str) -> Option<f64> {
+    let modified = fs::metadata(path).ok()?.modified().ok()?;
+    modified.duration_since(UNIX_EPOCH).ok().map(|x| x.as_secs_f64())
+}
@@ -0,0 +1,31 @@
+/// Scores `candidate` against `pattern` as a case-insensitive subsequence match.
+/// Consecutive matches and matches at the start of a word score higher, and an
+/// empty pattern matches everything. Returns None if `pattern` doesn't match.
+pub fn score(pattern: &str, candidate: &str) -> Option<i32> {
+    let candidate: Vec::<char> = candidate.to_lowercase().chars().collect();
+    let mut score = 0;
+    let mut index = 0;
+    let mut last_match: Option<usize> = None;
+
+    for p in pattern.to_lowercase().chars().filter(|x| !x.is_whitespace()) {
+        match candidate[index..].iter().position(|x| *x == p) {
+            Some(offset) => {
+                let found = index + offset;
+                score = score + 1;
+                if found > 0 && last_match == Some(found - 1) {
+                    score = score + 5;
+                }
+                if found == 0 || !candidate[found - 1].is_alphanumeric() {
+                    score = score + 3;
+                }
+
+                last_match = Some(found);
+                index = found + 1;
+            }
+            None => return None
+        }
+    }
+
+    // prefer shorter candidates when everything else is equal
+    Some(score * 100 - candidate.len() as i32)
+}
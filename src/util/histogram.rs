@@ -0,0 +1,103 @@
+use image::{ Rgba, RgbaImage };
+
+// a channel at or past these counts as clipped, blown out or crushed to black
+const HIGHLIGHT_LEVEL: u8 = 255;
+const SHADOW_LEVEL: u8 = 2;
+
+const BACKGROUND: Rgba<u8> = Rgba([30, 30, 30, 255]);
+const LUMA_COLOR: Rgba<u8> = Rgba([90, 90, 90, 255]);
+const HIGHLIGHT_COLOR: Rgba<u8> = Rgba([255, 0, 0, 255]);
+const SHADOW_COLOR: Rgba<u8> = Rgba([0, 80, 255, 255]);
+
+// Rec. 709 weights, the same the image crate uses for grayscale
+fn luma(pixel: &Rgba<u8>) -> u8 {
+    ((2126 * pixel[0] as u32 + 7152 * pixel[1] as u32 + 722 * pixel[2] as u32) / 10000) as u8
+}
+
+fn is_highlight(pixel: &Rgba<u8>) -> bool {
+    pixel[0] >= HIGHLIGHT_LEVEL || pixel[1] >= HIGHLIGHT_LEVEL || pixel[2] >= HIGHLIGHT_LEVEL
+}
+
+fn is_shadow(pixel: &Rgba<u8>) -> bool {
+    pixel[0] <= SHADOW_LEVEL && pixel[1] <= SHADOW_LEVEL && pixel[2] <= SHADOW_LEVEL
+}
+
+#[derive(Debug, Clone)]
+pub struct Histogram {
+    pub red: [u32; 256],
+    pub green: [u32; 256],
+    pub blue: [u32; 256],
+    pub luma: [u32; 256],
+    // fractions of the image clipped at either end
+    pub highlights: f64,
+    pub shadows: f64,
+}
+
+impl Histogram {
+    // fully transparent pixels aren't part of the picture, so they're left out
+    pub fn compute(image: &RgbaImage) -> Histogram {
+        let mut histogram = Histogram {
+            red: [0; 256],
+            green: [0; 256],
+            blue: [0; 256],
+            luma: [0; 256],
+            highlights: 0.0,
+            shadows: 0.0
+        };
+
+        let (mut highlights, mut shadows, mut total) = (0, 0, 0);
+        for pixel in image.pixels().filter(|x| x[3] > 0) {
+            histogram.red[pixel[0] as usize] += 1;
+            histogram.green[pixel[1] as usize] += 1;
+            histogram.blue[pixel[2] as usize] += 1;
+            histogram.luma[luma(pixel) as usize] += 1;
+            highlights += is_highlight(pixel) as u32;
+            shadows += is_shadow(pixel) as u32;
+            total += 1;
+        }
+
+        if total > 0 {
+            histogram.highlights = highlights as f64 / total as f64;
+            histogram.shadows = shadows as f64 / total as f64;
+        }
+        histogram
+    }
+
+    // The luma histogram in gray with the colour channels added on top of it, so where
+    // all three overlap shows white. Scaled to the tallest bin short of the two ends,
+    // which would otherwise flatten the rest of the graph for a clipped image.
+    pub fn render(self: &Self, height: u32) -> RgbaImage {
+        let channels = [&self.red, &self.green, &self.blue, &self.luma];
+        let tallest = channels.iter().flat_map(|x| x[1..255].iter()).max().cloned().unwrap_or(0).max(1) as f64;
+        let bar = |count: u32| ((count as f64 / tallest).min(1.0) * height as f64).round() as u32;
+
+        let mut image = RgbaImage::from_pixel(256, height, BACKGROUND);
+        for x in 0..256 {
+            let heights: Vec::<u32> = channels.iter().map(|channel| bar(channel[x])).collect();
+            for y in 0..height {
+                let level = height - y;
+                let covered = |channel: usize| heights[channel] >= level;
+                let pixel = match (covered(0), covered(1), covered(2)) {
+                    (false, false, false) if covered(3) => LUMA_COLOR,
+                    (false, false, false) => BACKGROUND,
+                    (red, green, blue) => Rgba([red as u8 * 220, green as u8 * 220, blue as u8 * 220, 255])
+                };
+                image.put_pixel(x as u32, y, pixel);
+            }
+        }
+        image
+    }
+}
+
+// the image with blown highlights painted red and crushed shadows blue
+pub fn clipping_overlay(image: &RgbaImage) -> RgbaImage {
+    let mut overlay = image.clone();
+    for pixel in overlay.pixels_mut().filter(|x| x[3] > 0) {
+        if is_highlight(pixel) {
+            *pixel = HIGHLIGHT_COLOR;
+        } else if is_shadow(pixel) {
+            *pixel = SHADOW_COLOR;
+        }
+    }
+    overlay
+}
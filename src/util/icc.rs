@@ -0,0 +1,270 @@
+use image::RgbaImage;
+use std::fs;
+
+use crate::util::{ animation, companions, exif::{ self, Tiff } };
+
+const TAG_ICC_PROFILE: u16 = 0x8773;
+
+// what sRGB's red, green and blue are in the D50 connection space profiles use
+const SRGB_COLORANTS: [[f64; 3]; 3] = [[0.4361, 0.2225, 0.0139],
+                                       [0.3851, 0.7169, 0.0971],
+                                       [0.1431, 0.0606, 0.7141]];
+// from the D50 connection space to linear sRGB, Bradford adapted
+const XYZ_TO_SRGB: [[f64; 3]; 3] = [[3.1338561, -1.6168667, -0.4906146],
+                                    [-0.9787684, 1.9161415, 0.0334540],
+                                    [0.0719453, -0.2289914, 1.4052427]];
+const ENCODE_STEPS: usize = 4096;
+
+fn u16_at(data: &[u8], offset: usize) -> Option<u16> {
+    let bytes = data.get(offset..offset + 2)?;
+    Some(u16::from_be_bytes([bytes[0], bytes[1]]))
+}
+
+fn u32_at(data: &[u8], offset: usize) -> Option<u32> {
+    let bytes = data.get(offset..offset + 4)?;
+    Some(u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+}
+
+// s15Fixed16Number, the fixed point most ICC values come as
+fn fixed_at(data: &[u8], offset: usize) -> Option<f64> {
+    u32_at(data, offset).map(|x| x as i32 as f64 / 65536.0)
+}
+
+fn srgb_decode(value: f64) -> f64 {
+    match value <= 0.04045 {
+        true => value / 12.92,
+        false => ((value + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+fn srgb_encode(value: f64) -> f64 {
+    match value <= 0.0031308 {
+        true => value * 12.92,
+        false => 1.055 * value.powf(1.0 / 2.4) - 0.055
+    }
+}
+
+// a tone response curve, from the encoded value to linear light
+enum Curve {
+    Gamma(f64),
+    Table(Vec::<f64>),
+    // the ICC parametric function type with its parameters g, a, b, c, d, e, f
+    Parametric(u16, [f64; 7]),
+}
+
+impl Curve {
+    fn parse(data: &[u8]) -> Option<Curve> {
+        match data.get(0..4)? {
+            b"curv" => match u32_at(data, 8)? {
+                0 => Some(Curve::Gamma(1.0)),
+                1 => Some(Curve::Gamma(u16_at(data, 12)? as f64 / 256.0)),
+                count => (0..count as usize).map(|i| u16_at(data, 12 + i * 2).map(|x| x as f64 / 65535.0))
+                                            .collect::<Option<Vec::<f64>>>()
+                                            .map(Curve::Table)
+            },
+            b"para" => {
+                let kind = u16_at(data, 8)?;
+                let count = [1, 3, 4, 5, 7].get(kind as usize)?;
+                let mut parameters = [0.0; 7];
+                for i in 0..*count {
+                    parameters[i] = fixed_at(data, 12 + i * 4)?;
+                }
+                Some(Curve::Parametric(kind, parameters))
+            }
+            _ => None
+        }
+    }
+
+    fn apply(self: &Self, x: f64) -> f64 {
+        match self {
+            Curve::Gamma(gamma) => x.powf(*gamma),
+            Curve::Table(table) => {
+                let position = x * (table.len() - 1) as f64;
+                let index = (position.floor() as usize).min(table.len() - 1);
+                let next = (index + 1).min(table.len() - 1);
+                table[index] + (table[next] - table[index]) * (position - index as f64)
+            }
+            Curve::Parametric(kind, [g, a, b, c, d, e, f]) => {
+                let power = |x: f64| (a * x + b).max(0.0).powf(*g);
+                match kind {
+                    0 => x.powf(*g),
+                    1 if x >= -b / a => power(x),
+                    1 => 0.0,
+                    2 if x >= -b / a => power(x) + c,
+                    2 => *c,
+                    3 if x >= *d => power(x),
+                    3 => c * x,
+                    _ if x >= *d => power(x) + e,
+                    _ => c * x + f
+                }
+            }
+        }
+    }
+}
+
+// An RGB display profile described by its colorants and tone curves, like Adobe RGB
+// and Display P3. Profiles built from lookup tables, like most CMYK ones, aren't read.
+pub struct Profile {
+    colorants: [[f64; 3]; 3],
+    curves: [Curve; 3],
+}
+
+impl Profile {
+    pub fn parse(data: &[u8]) -> Option<Profile> {
+        if data.get(36..40)? != b"acsp" || data.get(16..20)? != b"RGB " || data.get(20..24)? != b"XYZ " {
+            return None;
+        }
+
+        let count = u32_at(data, 128)? as usize;
+        let tag = |signature: &[u8]| (0..count).find_map(|i| {
+            let entry = 132 + i * 12;
+            if data.get(entry..entry + 4)? != signature {
+                return None;
+            }
+            let offset = u32_at(data, entry + 4)? as usize;
+            data.get(offset..offset + u32_at(data, entry + 8)? as usize)
+        });
+        let colorant = |signature: &[u8]| -> Option<[f64; 3]> {
+            let data = tag(signature).filter(|x| x.starts_with(b"XYZ "))?;
+            Some([fixed_at(data, 8)?, fixed_at(data, 12)?, fixed_at(data, 16)?])
+        };
+
+        Some(Profile {
+            colorants: [colorant(b"rXYZ")?, colorant(b"gXYZ")?, colorant(b"bXYZ")?],
+            curves: [Curve::parse(tag(b"rTRC")?)?, Curve::parse(tag(b"gTRC")?)?, Curve::parse(tag(b"bTRC")?)?]
+        })
+    }
+
+    // close enough to sRGB that converting would change nothing visible
+    fn is_srgb(self: &Self) -> bool {
+        let same_colorants = self.colorants.iter()
+                                           .flatten()
+                                           .zip(SRGB_COLORANTS.iter().flatten())
+                                           .all(|(a, b)| (a - b).abs() < 0.003);
+        let same_curves = self.curves.iter()
+                                     .all(|curve| [0.25, 0.5, 0.75].iter().all(|x| (curve.apply(*x) - srgb_decode(*x)).abs() < 0.01));
+        same_colorants && same_curves
+    }
+
+    pub fn to_srgb(self: &Self, image: &mut RgbaImage) {
+        // the profile's colorants folded into the conversion, so each pixel takes one multiply
+        let mut matrix = [[0.0; 3]; 3];
+        for row in 0..3 {
+            for column in 0..3 {
+                matrix[row][column] = (0..3).map(|k| XYZ_TO_SRGB[row][k] * self.colorants[column][k]).sum();
+            }
+        }
+        let linear: Vec::<Vec::<f64>> = self.curves
+                                            .iter()
+                                            .map(|curve| (0..256).map(|x| curve.apply(x as f64 / 255.0)).collect())
+                                            .collect();
+        let encode: Vec::<u8> = (0..ENCODE_STEPS).map(|x| {
+                                                     let value = srgb_encode(x as f64 / (ENCODE_STEPS - 1) as f64);
+                                                     (value * 255.0).round() as u8
+                                                 })
+                                                 .collect();
+
+        for pixel in image.pixels_mut() {
+            let input = [linear[0][pixel[0] as usize], linear[1][pixel[1] as usize], linear[2][pixel[2] as usize]];
+            for channel in 0..3 {
+                let value = (0..3).map(|k| matrix[channel][k] * input[k]).sum::<f64>();
+                // colours outside sRGB are clipped to its edge
+                pixel[channel] = encode[(value.max(0.0).min(1.0) * (ENCODE_STEPS - 1) as f64).round() as usize];
+            }
+        }
+    }
+}
+
+// ISOBMFF boxes, as HEIC and AVIF are made of, are a size covering the header, then
+// the type and the contents. Sizes of 1 (a 64 bit size follows) and 0 (to the end of
+// the file) are only used for the image data, so whatever follows them is dropped.
+fn isobmff_boxes(data: &[u8]) -> Vec::<(&[u8], &[u8])> {
+    let mut boxes = Vec::<(&[u8], &[u8])>::new();
+    let mut position = 0;
+    while let (Some(size), Some(name)) = (u32_at(data, position), data.get(position + 4..position + 8)) {
+        match data.get(position + 8..position + size as usize) {
+            Some(contents) if size >= 8 => boxes.push((name, contents)),
+            _ => break
+        }
+        position = position + size as usize;
+    }
+    boxes
+}
+
+fn isobmff_child<'a>(data: &'a [u8], name: &[u8]) -> Option<&'a [u8]> {
+    isobmff_boxes(data).into_iter().find(|(x, _)| *x == name).map(|(_, contents)| contents)
+}
+
+// the ICC profile a file carries, from wherever its format keeps it, page only matters for multi-page TIFFs
+pub fn embedded_profile(path: &str, page: usize) -> Option<Vec::<u8>> {
+    match companions::extension(path).as_str() {
+        "jpg" | "jpeg" => {
+            let header = exif::read_header(path)?;
+            // profiles too big for one segment are split across several, numbered from 1
+            let mut parts: Vec::<(u8, &[u8])> = exif::jpeg_segments(&header)
+                                                    .into_iter()
+                                                    .filter(|(marker, contents)| *marker == 0xE2 && contents.len() > 14
+                                                                                 && contents.starts_with(b"ICC_PROFILE\0"))
+                                                    .map(|(_, contents)| (contents[12], &contents[14..]))
+                                                    .collect();
+            parts.sort_by_key(|(number, _)| *number);
+            match parts.is_empty() {
+                true => None,
+                false => Some(parts.iter().flat_map(|(_, part)| part.iter().cloned()).collect())
+            }
+        }
+        "png" => {
+            let header = exif::read_header(path)?;
+            // after the signature each chunk is its length, type, data and a checksum
+            let mut position = 8;
+            while let Some(length) = u32_at(&header, position) {
+                match header.get(position + 4..position + 8)? {
+                    b"iCCP" => {
+                        let data = header.get(position + 8..position + 8 + length as usize)?;
+                        // a name, then the compression method, then the zlib compressed profile
+                        let name_end = data.iter().position(|x| *x == 0)?;
+                        return miniz_oxide::inflate::decompress_to_vec_zlib(data.get(name_end + 2..)?).ok();
+                    }
+                    b"IDAT" => return None,
+                    _ => ()
+                }
+                position = position + 12 + length as usize;
+            }
+            None
+        }
+        // TIFFs often keep their tags after the image data, so the whole file is needed
+        "tif" | "tiff" => {
+            let data = fs::read(path).ok()?;
+            let tiff = Tiff::new(&data)?;
+            // each page has its own IFD, and can have its own profile
+            let ifd = (0..page).try_fold(tiff.first_ifd()?, |ifd, _| tiff.next_ifd(ifd))?;
+            let entry = tiff.find(ifd, TAG_ICC_PROFILE)?;
+            tiff.bytes(&entry).map(|x| x.to_vec())
+        }
+        "webp" => {
+            let header = exif::read_header(path)?;
+            animation::riff_chunks(header.get(12..)?).into_iter()
+                                                     .find(|(name, _)| *name == b"ICCP")
+                                                     .map(|(_, profile)| profile.to_vec())
+        }
+        // properties of the images are in meta/iprp/ipco, a colr box there of type prof or
+        // rICC holds a profile, which cameras and phones give every image in the file
+        "heic" | "heif" | "avif" => {
+            let header = exif::read_header(path)?;
+            // meta is a full box, its version and flags come before the boxes in it
+            let meta = isobmff_child(&header, b"meta")?;
+            let properties = isobmff_child(isobmff_child(meta.get(4..)?, b"iprp")?, b"ipco")?;
+            isobmff_boxes(properties).into_iter()
+                                     .filter(|(name, contents)| *name == b"colr" && contents.len() > 4)
+                                     .find(|(_, contents)| contents[0..4] == *b"prof" || contents[0..4] == *b"rICC")
+                                     .map(|(_, contents)| contents[4..].to_vec())
+        }
+        _ => None
+    }
+}
+
+// the profile to convert a file's pixels with, None when it has none, isn't one
+// that can be read or already is sRGB
+pub fn profile(path: &str, page: usize) -> Option<Profile> {
+    Profile::parse(&embedded_profile(path, page)?).filter(|x| !x.is_srgb())
+}
@@ -1,7 +1,201 @@
-use iced::{ Length, Image, Container };
+use iced::{ image, svg, Length, Image, Svg, Container };
+use ::image::{ DynamicImage, RgbaImage };
+use std::{ fs::{ self, File }, time::Duration };
 
-pub fn load_image<'a, T>(src: String) -> Container<'a, T> {
-    Container::new(Image::new(src))
-        .height(Length::Shrink)
-        .center_x()
+use crate::util::{ animation, companions, icc, raw::{ self, Preview } };
+
+const SVG_EXTENSIONS: [&str; 2] = ["svg", "svgz"];
+const TIFF_EXTENSIONS: [&str; 2] = ["tif", "tiff"];
+// HEIC from phones and AVIF share the HEIF container, libheif reads both
+const HEIF_EXTENSIONS: [&str; 3] = ["heic", "heif", "avif"];
+
+#[derive(Debug, Clone)]
+pub struct AnimationFrame {
+    pub handle: image::Handle,
+    pub delay: Duration,
+}
+
+#[derive(Debug, Clone)]
+pub enum ImageContent {
+    Raster(image::Handle),
+    Vector(svg::Handle),
+    Animation(Vec::<AnimationFrame>),
+}
+
+pub struct LoadedImage {
+    pub content: ImageContent,
+    pub pages: usize,
+    // what's shown, or the first frame of an animation
+    pub pixels: Option<RgbaImage>,
+}
+
+// iced takes raw pixels as BGRA
+pub fn rgba_handle(image: RgbaImage) -> image::Handle {
+    let (width, height) = image.dimensions();
+    let mut pixels = image.into_raw();
+    for pixel in pixels.chunks_mut(4) {
+        pixel.swap(0, 2);
+    }
+    image::Handle::from_pixels(width, height, pixels)
+}
+
+// one page of a TIFF along with how many pages it has, the image crate only reads the first
+fn tiff_page(path: &str, page: usize) -> Result<(RgbaImage, usize), String> {
+    use tiff::{ decoder::{ Decoder, DecodingResult }, ColorType };
+
+    let file = File::open(path).map_err(|e| e.to_string())?;
+    let mut decoder = Decoder::new(file).map_err(|e| e.to_string())?;
+    let mut pages = 1;
+    let mut image = None;
+    loop {
+        if pages - 1 == page {
+            let (width, height) = decoder.dimensions().map_err(|e| e.to_string())?;
+            let channels = match decoder.colortype().map_err(|e| e.to_string())? {
+                ColorType::Gray(_) => 1,
+                ColorType::GrayA(_) => 2,
+                ColorType::RGB(_) => 3,
+                ColorType::RGBA(_) => 4,
+                other => return Err(format!("unsupported TIFF colour type {:?}", other))
+            };
+            let samples: Vec::<u8> = match decoder.read_image().map_err(|e| e.to_string())? {
+                DecodingResult::U8(x) => x,
+                DecodingResult::U16(x) => x.iter().map(|x| (x >> 8) as u8).collect(),
+                _ => return Err(String::from("unsupported TIFF sample format"))
+            };
+            let rgba: Vec::<u8> = samples.chunks(channels)
+                                         .flat_map(|x| match channels {
+                                             1 => vec![x[0], x[0], x[0], 255],
+                                             2 => vec![x[0], x[0], x[0], x[1]],
+                                             3 => vec![x[0], x[1], x[2], 255],
+                                             _ => x.to_vec()
+                                         })
+                                         .collect();
+            image = RgbaImage::from_raw(width, height, rgba);
+        }
+        if !decoder.more_images() || decoder.next_image().is_err() {
+            break;
+        }
+        pages = pages + 1;
+    }
+
+    match image {
+        Some(image) => Ok((image, pages)),
+        None if page >= pages => Err(format!("page {} of {} doesn't exist", page + 1, pages)),
+        None => Err(String::from("TIFF page has the wrong size"))
+    }
+}
+
+#[cfg(feature = "heif")]
+fn heif_image(path: &str) -> Result<RgbaImage, String> {
+    use libheif_rs::{ HeifContext, ColorSpace, RgbChroma };
+
+    let context = HeifContext::read_from_file(path).map_err(|e| e.to_string())?;
+    let handle = context.primary_image_handle().map_err(|e| e.to_string())?;
+    let image = handle.decode(ColorSpace::Rgb(RgbChroma::Rgba), false).map_err(|e| e.to_string())?;
+    let plane = image.planes().interleaved.ok_or(String::from("no RGBA pixels decoded"))?;
+
+    // rows can be padded past the width
+    let mut rgba = Vec::<u8>::with_capacity((plane.width * plane.height * 4) as usize);
+    for row in plane.data.chunks(plane.stride).take(plane.height as usize) {
+        rgba.extend_from_slice(&row[..(plane.width * 4) as usize]);
+    }
+    RgbaImage::from_raw(plane.width, plane.height, rgba).ok_or(String::from("HEIF image has the wrong size"))
+}
+
+#[cfg(not(feature = "heif"))]
+fn heif_image(_path: &str) -> Result<RgbaImage, String> {
+    Err(String::from("HEIC and AVIF need the heif feature, build with --features heif"))
+}
+
+// The image to show for a file, page only matters for multi-page TIFFs. Images are
+// decoded here rather than by iced so a file it can't read shows an error instead of
+// nothing, and so the pixels are there for the histogram.
+pub fn load(path: &str, page: usize) -> Result<LoadedImage, String> {
+    let extension = companions::extension(path);
+    if SVG_EXTENSIONS.contains(&extension.as_str()) {
+        return Ok(LoadedImage { content: ImageContent::Vector(svg::Handle::from_path(path)), pages: 1, pixels: None });
+    }
+
+    // pixels are shown as sRGB, so ones in a wider space like Adobe RGB are converted first
+    let profile = icc::profile(path, page);
+    let to_srgb = |image: &mut RgbaImage| {
+        if let Some(profile) = &profile {
+            profile.to_srgb(image);
+        }
+    };
+
+    if let Some(mut frames) = animation::frames(path)? {
+        frames.iter_mut().for_each(|x| to_srgb(&mut x.image));
+        let first = frames[0].image.clone();
+        let frames = frames.into_iter()
+                           .map(|x| AnimationFrame { handle: rgba_handle(x.image), delay: x.delay })
+                           .collect();
+        return Ok(LoadedImage { content: ImageContent::Animation(frames), pages: 1, pixels: Some(first) });
+    }
+
+    let (mut image, pages) = match TIFF_EXTENSIONS.contains(&extension.as_str()) {
+        true => tiff_page(path, page)?,
+        false => (decode(path)?.to_rgba8(), 1)
+    };
+    to_srgb(&mut image);
+    Ok(LoadedImage { content: ImageContent::Raster(rgba_handle(image.clone())), pages: pages, pixels: Some(image) })
+}
+
+// the pixels of an image for the tools that look at them, the first page, frame or the preview where there's a choice
+pub fn decode(path: &str) -> Result<DynamicImage, String> {
+    let extension = companions::extension(path);
+    match extension.as_str() {
+        x if SVG_EXTENSIONS.contains(&x) => Err(String::from("vector images have no pixels to compare")),
+        x if TIFF_EXTENSIONS.contains(&x) => tiff_page(path, 0).map(|(image, _)| DynamicImage::ImageRgba8(image)),
+        x if HEIF_EXTENSIONS.contains(&x) => heif_image(path).map(DynamicImage::ImageRgba8),
+        // the image crate can't read animated WebPs itself
+        "webp" => match animation::frames(path)? {
+            Some(frames) => Ok(DynamicImage::ImageRgba8(frames.into_iter().next().unwrap().image)),
+            None => ::image::open(path).map_err(|e| e.to_string())
+        },
+        _ if raw::is_raw(path) => match raw::preview(path)? {
+            Preview::Jpeg(bytes) => ::image::load_from_memory(&bytes).map_err(|e| e.to_string()),
+            Preview::Rgb { width, height, pixels } => ::image::RgbImage::from_raw(width, height, pixels)
+                                                          .map(DynamicImage::ImageRgb8)
+                                                          .ok_or(String::from("preview has the wrong size"))
+        },
+        _ => ::image::open(path).map_err(|e| e.to_string())
+    }
+}
+
+// Fully decodes a file to find ones that are corrupt or were cut short while copying.
+// Files that aren't images at all pass, there's nothing to check them against.
+pub fn verify(path: &str) -> Result<(), String> {
+    let extension = companions::extension(path);
+    match extension.as_str() {
+        "svg" => match fs::read_to_string(path).map_err(|e| e.to_string())?.contains("</svg>") {
+            true => Ok(()),
+            false => Err(String::from("SVG ends before its closing </svg>"))
+        },
+        "svgz" => fs::metadata(path).map(|_| ()).map_err(|e| e.to_string()),
+        // a JPEG decoder fills in whatever is missing from a cut short file, so look for the end marker
+        "jpg" | "jpeg" => match raw::jpeg_end(&fs::read(path).map_err(|e| e.to_string())?, 0) {
+            Some(_) => decode(path).map(|_| ()),
+            None => Err(String::from("JPEG data ends early or is damaged"))
+        },
+        // can't be decoded at all without the feature, which doesn't make them corrupt
+        x if HEIF_EXTENSIONS.contains(&x) && !cfg!(feature = "heif") => Ok(()),
+        x if TIFF_EXTENSIONS.contains(&x) || HEIF_EXTENSIONS.contains(&x) || raw::is_raw(path) => decode(path).map(|_| ()),
+        _ if ::image::ImageFormat::from_path(path).is_err() => Ok(()),
+        // every frame of an animation, not just the first
+        _ => match animation::frames(path)? {
+            Some(_) => Ok(()),
+            None => decode(path).map(|_| ())
+        }
+    }
+}
+
+pub fn load_image<'a, T: 'a>(content: &ImageContent, frame: usize) -> Container<'a, T> {
+    match content {
+        ImageContent::Raster(handle) => Container::new(Image::new(handle.clone())),
+        ImageContent::Vector(handle) => Container::new(Svg::new(handle.clone())),
+        ImageContent::Animation(frames) => Container::new(Image::new(frames[frame.min(frames.len() - 1)].handle.clone()))
+    }
+    .height(Length::Shrink)
+    .center_x()
 }
@@ -0,0 +1,526 @@
+use iced_native::keyboard::{ self, KeyCode, ModifiersState };
+use serde::Deserialize;
+use std::{ fs, io, collections::HashMap, path::{ Path, PathBuf } };
+
+const APP_DIRECTORY: &str = "image_organizer";
+const KEYMAP_FILE: &str = "keys.toml";
+
+// Global actions work in the tagging view and in the side panel. Letters that
+// aren't bound to a tagging or global action are left free for tags.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KeyContext {
+    Global,
+    Tagging,
+    SidePanel,
+    Menu,
+}
+
+impl KeyContext {
+    fn overlaps(self: &Self, other: &KeyContext) -> bool {
+        match (self, other) {
+            (KeyContext::Menu, KeyContext::Menu) => true,
+            (KeyContext::Menu, _) | (_, KeyContext::Menu) => false,
+            (KeyContext::Global, _) | (_, KeyContext::Global) => true,
+            _ => self == other
+        }
+    }
+
+    pub fn label(self: &Self) -> &'static str {
+        match self {
+            KeyContext::Global => "Anywhere",
+            KeyContext::Tagging => "Tagging",
+            KeyContext::SidePanel => "Side Panel",
+            KeyContext::Menu => "Menu"
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KeyAction {
+    OpenMenu,
+    Undo,
+    Redo,
+    Help,
+    CommandPalette,
+    NextImage,
+    PrevImage,
+    NextUntagged,
+    PrevUntagged,
+    NextTagged,
+    PrevTagged,
+    FirstImage,
+    LastImage,
+    NextWithTag,
+    PrevWithTag,
+    FindSimilar,
+    NextSimilar,
+    PrevSimilar,
+    TagCluster,
+    ToggleStack,
+    PickStack,
+    NextPage,
+    PrevPage,
+    ToggleAnimation,
+    NextFrame,
+    PrevFrame,
+    ToggleClipping,
+    MarkForDeletion,
+    RestoreDeleted,
+    KeepCopy,
+    SearchTags,
+    FocusSidePanel,
+    Filter,
+    ClearFilter,
+    SelectTagUp,
+    SelectTagDown,
+    MoveTagUp,
+    MoveTagDown,
+    RenameTag,
+    RebindTag,
+    CycleColor,
+    CycleOrder,
+    DeleteTag,
+    LeaveSidePanel,
+    CloseMenu,
+    Quit,
+    CycleOrganizeMode,
+    RunOrganize,
+    ToggleOrganizeFiltered,
+    ToggleOrganizeCorrupt,
+    FindDuplicates,
+    ClusterSimilar,
+    StackBursts,
+    ClearConsole,
+    SaveSession,
+    LoadSession,
+    MergeGlobalPreset,
+    MergeProjectPreset,
+    WriteProjectPreset,
+    ExportGlobalPreset,
+}
+
+impl KeyAction {
+    // also the order bindings are listed in and conflicts are resolved in
+    pub const ALL: [KeyAction; 60] = [
+        KeyAction::OpenMenu, KeyAction::Undo, KeyAction::Redo, KeyAction::Help, KeyAction::CommandPalette,
+        KeyAction::NextImage, KeyAction::PrevImage, KeyAction::NextUntagged, KeyAction::PrevUntagged,
+        KeyAction::NextTagged, KeyAction::PrevTagged, KeyAction::FirstImage, KeyAction::LastImage,
+        KeyAction::NextWithTag, KeyAction::PrevWithTag, KeyAction::FindSimilar, KeyAction::NextSimilar,
+        KeyAction::PrevSimilar, KeyAction::TagCluster, KeyAction::ToggleStack, KeyAction::PickStack,
+        KeyAction::NextPage, KeyAction::PrevPage, KeyAction::ToggleAnimation, KeyAction::NextFrame, KeyAction::PrevFrame,
+        KeyAction::ToggleClipping,
+        KeyAction::MarkForDeletion, KeyAction::RestoreDeleted, KeyAction::KeepCopy, KeyAction::SearchTags,
+        KeyAction::FocusSidePanel, KeyAction::Filter, KeyAction::ClearFilter,
+        KeyAction::SelectTagUp, KeyAction::SelectTagDown, KeyAction::MoveTagUp, KeyAction::MoveTagDown,
+        KeyAction::RenameTag, KeyAction::RebindTag, KeyAction::CycleColor, KeyAction::CycleOrder,
+        KeyAction::DeleteTag, KeyAction::LeaveSidePanel,
+        KeyAction::CloseMenu, KeyAction::Quit, KeyAction::CycleOrganizeMode, KeyAction::RunOrganize,
+        KeyAction::ToggleOrganizeFiltered, KeyAction::ToggleOrganizeCorrupt, KeyAction::FindDuplicates, KeyAction::ClusterSimilar,
+        KeyAction::StackBursts, KeyAction::ClearConsole,
+        KeyAction::SaveSession, KeyAction::LoadSession,
+        KeyAction::MergeGlobalPreset, KeyAction::MergeProjectPreset,
+        KeyAction::WriteProjectPreset, KeyAction::ExportGlobalPreset,
+    ];
+
+    // (name in the config file, context, default keys, description)
+    fn info(self: &Self) -> (&'static str, KeyContext, &'static [&'static str], &'static str) {
+        match self {
+            KeyAction::OpenMenu => ("open_menu", KeyContext::Global, &["Escape"], "Show menu"),
+            KeyAction::Undo => ("undo", KeyContext::Global, &["Ctrl+Z"], "Undo"),
+            KeyAction::Redo => ("redo", KeyContext::Global, &["Ctrl+Shift+Z"], "Redo"),
+            KeyAction::Help => ("help", KeyContext::Global, &["?"], "Show or hide this help"),
+            KeyAction::CommandPalette => ("command_palette", KeyContext::Global, &[":"], "Run any command by name"),
+            KeyAction::NextImage => ("next_image", KeyContext::Tagging, &["Right"], "Next image (a count like 5 first skips 5)"),
+            KeyAction::PrevImage => ("prev_image", KeyContext::Tagging, &["Left"], "Previous image"),
+            KeyAction::NextUntagged => ("next_untagged", KeyContext::Tagging, &["]"], "Next untagged image"),
+            KeyAction::PrevUntagged => ("prev_untagged", KeyContext::Tagging, &["["], "Previous untagged image"),
+            KeyAction::NextTagged => ("next_tagged", KeyContext::Tagging, &["}"], "Next tagged image"),
+            KeyAction::PrevTagged => ("prev_tagged", KeyContext::Tagging, &["{"], "Previous tagged image"),
+            KeyAction::FirstImage => ("first_image", KeyContext::Tagging, &["gg", "Home"], "First image, or image N after a count"),
+            KeyAction::LastImage => ("last_image", KeyContext::Tagging, &["G", "End"], "Last image, or image N after a count"),
+            KeyAction::NextWithTag => ("next_with_tag", KeyContext::Tagging, &["'"], "Next image with the tag whose key follows"),
+            KeyAction::PrevWithTag => ("prev_with_tag", KeyContext::Tagging, &["\""], "Previous image with the tag whose key follows"),
+            KeyAction::FindSimilar => ("find_similar", KeyContext::Tagging, &["~"], "Find images that look like this one"),
+            KeyAction::NextSimilar => ("next_similar", KeyContext::Tagging, &[")"], "Next image in this cluster of similar images"),
+            KeyAction::PrevSimilar => ("prev_similar", KeyContext::Tagging, &["("], "Previous image in this cluster of similar images"),
+            KeyAction::TagCluster => ("tag_cluster", KeyContext::Tagging, &[";"], "Toggle the tag whose key follows on the whole cluster"),
+            KeyAction::ToggleStack => ("toggle_stack", KeyContext::Tagging, &["*"], "Expand or collapse this burst stack"),
+            KeyAction::PickStack => ("pick_stack", KeyContext::Tagging, &["^"], "Show this image on the collapsed stack"),
+            KeyAction::NextPage => ("next_page", KeyContext::Tagging, &["PageDown"], "Next page of a multi-page TIFF"),
+            KeyAction::PrevPage => ("prev_page", KeyContext::Tagging, &["PageUp"], "Previous page of a multi-page TIFF"),
+            KeyAction::ToggleAnimation => ("toggle_animation", KeyContext::Tagging, &["Space"], "Pause or play an animated image"),
+            KeyAction::NextFrame => ("next_frame", KeyContext::Tagging, &["."], "Pause on the next frame of an animation"),
+            KeyAction::PrevFrame => ("prev_frame", KeyContext::Tagging, &[","], "Pause on the previous frame of an animation"),
+            KeyAction::ToggleClipping => ("toggle_clipping", KeyContext::Tagging, &["!"], "Mark clipped highlights red and shadows blue"),
+            KeyAction::MarkForDeletion => ("mark_for_deletion", KeyContext::Tagging, &["Delete"], "Mark image for deletion"),
+            KeyAction::RestoreDeleted => ("restore_deleted", KeyContext::Tagging, &["Backspace"], "Restore last marked image"),
+            KeyAction::KeepCopy => ("keep_copy", KeyContext::Tagging, &["Ctrl+D"], "Keep this image and mark its identical copies for deletion"),
+            KeyAction::SearchTags => ("search_tags", KeyContext::Tagging, &["/"], "Search tags"),
+            KeyAction::FocusSidePanel => ("focus_side_panel", KeyContext::Tagging, &["Tab"], "Edit tags in the side panel"),
+            KeyAction::Filter => ("filter", KeyContext::Tagging, &["="], "Only show images matching a query like cats AND NOT outdoor"),
+            KeyAction::ClearFilter => ("clear_filter", KeyContext::Tagging, &[], "Show every image again"),
+            KeyAction::SelectTagUp => ("select_tag_up", KeyContext::SidePanel, &["Up"], "Select previous tag"),
+            KeyAction::SelectTagDown => ("select_tag_down", KeyContext::SidePanel, &["Down"], "Select next tag"),
+            KeyAction::MoveTagUp => ("move_tag_up", KeyContext::SidePanel, &["Shift+Up"], "Move tag up"),
+            KeyAction::MoveTagDown => ("move_tag_down", KeyContext::SidePanel, &["Shift+Down"], "Move tag down"),
+            KeyAction::RenameTag => ("rename_tag", KeyContext::SidePanel, &["R"], "Rename (a/b moves under a)"),
+            KeyAction::RebindTag => ("rebind_tag", KeyContext::SidePanel, &["B"], "Rebind"),
+            KeyAction::CycleColor => ("cycle_color", KeyContext::SidePanel, &["C"], "Color"),
+            KeyAction::CycleOrder => ("cycle_order", KeyContext::SidePanel, &["O"], "Change order"),
+            KeyAction::DeleteTag => ("delete_tag", KeyContext::SidePanel, &["D", "Delete"], "Delete"),
+            KeyAction::LeaveSidePanel => ("leave_side_panel", KeyContext::SidePanel, &["Tab"], "Done"),
+            KeyAction::CloseMenu => ("close_menu", KeyContext::Menu, &["Escape"], "Close Menu"),
+            KeyAction::Quit => ("quit", KeyContext::Menu, &["Q"], "Quit"),
+            KeyAction::CycleOrganizeMode => ("cycle_organize_mode", KeyContext::Menu, &["O"], "Organize Mode"),
+            KeyAction::RunOrganize => ("run_organize", KeyContext::Menu, &["R"], "Run Organize Process"),
+            KeyAction::ToggleOrganizeFiltered => ("toggle_organize_filtered", KeyContext::Menu, &["F"], "Organize Only Filtered Images"),
+            KeyAction::ToggleOrganizeCorrupt => ("toggle_organize_corrupt", KeyContext::Menu, &["X"], "Organize Corrupt Images Into corrupt/"),
+            KeyAction::FindDuplicates => ("find_duplicates", KeyContext::Menu, &["D"], "Find Identical Images"),
+            KeyAction::ClusterSimilar => ("cluster_similar", KeyContext::Menu, &["V"], "Cluster Visually Similar Images"),
+            KeyAction::StackBursts => ("stack_bursts", KeyContext::Menu, &["B"], "Stack Bursts of Shots"),
+            KeyAction::ClearConsole => ("clear_console", KeyContext::Menu, &["C"], "Clear Console"),
+            KeyAction::SaveSession => ("save_session", KeyContext::Menu, &["S"], "Save Session"),
+            KeyAction::LoadSession => ("load_session", KeyContext::Menu, &["L"], "Load Session"),
+            KeyAction::MergeGlobalPreset => ("merge_global_preset", KeyContext::Menu, &["G"], "Merge Global Tag Preset"),
+            KeyAction::MergeProjectPreset => ("merge_project_preset", KeyContext::Menu, &["P"], "Merge Project Tag Preset"),
+            KeyAction::WriteProjectPreset => ("write_project_preset", KeyContext::Menu, &["W"], "Write Project Tag Preset"),
+            KeyAction::ExportGlobalPreset => ("export_global_preset", KeyContext::Menu, &["E"], "Export Global Tag Preset"),
+        }
+    }
+
+    pub fn name(self: &Self) -> &'static str {
+        self.info().0
+    }
+
+    pub fn context(self: &Self) -> KeyContext {
+        self.info().1
+    }
+
+    pub fn description(self: &Self) -> &'static str {
+        self.info().3
+    }
+
+    fn from_name(name: &str) -> Option<KeyAction> {
+        KeyAction::ALL.iter().find(|x| x.name() == name).cloned()
+    }
+}
+
+// Characters are matched against typed text, so shifted symbols like '{' are
+// written as they are typed. Everything with Ctrl or Alt, and keys that don't
+// type anything, are matched by key code instead.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Key {
+    Char(char),
+    Code(KeyCode),
+}
+
+// a prefix makes a two key sequence like gg
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct KeyBinding {
+    prefix: Option<char>,
+    key: Key,
+    control: bool,
+    shift: bool,
+    alt: bool,
+    ignore_case: bool,
+}
+
+const NAMED_KEYS: [(&str, KeyCode); 27] = [
+    ("Left", KeyCode::Left), ("Right", KeyCode::Right), ("Up", KeyCode::Up), ("Down", KeyCode::Down),
+    ("Enter", KeyCode::Enter), ("Tab", KeyCode::Tab), ("Escape", KeyCode::Escape), ("Space", KeyCode::Space),
+    ("Delete", KeyCode::Delete), ("Backspace", KeyCode::Backspace), ("Insert", KeyCode::Insert),
+    ("Home", KeyCode::Home), ("End", KeyCode::End), ("PageUp", KeyCode::PageUp), ("PageDown", KeyCode::PageDown),
+    ("F1", KeyCode::F1), ("F2", KeyCode::F2), ("F3", KeyCode::F3), ("F4", KeyCode::F4),
+    ("F5", KeyCode::F5), ("F6", KeyCode::F6), ("F7", KeyCode::F7), ("F8", KeyCode::F8),
+    ("F9", KeyCode::F9), ("F10", KeyCode::F10), ("F11", KeyCode::F11), ("F12", KeyCode::F12),
+];
+
+fn char_key_code(character: char) -> Option<KeyCode> {
+    let code = match character.to_ascii_uppercase() {
+        'A' => KeyCode::A, 'B' => KeyCode::B, 'C' => KeyCode::C, 'D' => KeyCode::D, 'E' => KeyCode::E,
+        'F' => KeyCode::F, 'G' => KeyCode::G, 'H' => KeyCode::H, 'I' => KeyCode::I, 'J' => KeyCode::J,
+        'K' => KeyCode::K, 'L' => KeyCode::L, 'M' => KeyCode::M, 'N' => KeyCode::N, 'O' => KeyCode::O,
+        'P' => KeyCode::P, 'Q' => KeyCode::Q, 'R' => KeyCode::R, 'S' => KeyCode::S, 'T' => KeyCode::T,
+        'U' => KeyCode::U, 'V' => KeyCode::V, 'W' => KeyCode::W, 'X' => KeyCode::X, 'Y' => KeyCode::Y,
+        'Z' => KeyCode::Z,
+        '0' => KeyCode::Key0, '1' => KeyCode::Key1, '2' => KeyCode::Key2, '3' => KeyCode::Key3,
+        '4' => KeyCode::Key4, '5' => KeyCode::Key5, '6' => KeyCode::Key6, '7' => KeyCode::Key7,
+        '8' => KeyCode::Key8, '9' => KeyCode::Key9,
+        _ => return None
+    };
+
+    Some(code)
+}
+
+impl KeyBinding {
+    // "Ctrl+Shift+Z", "Left", "{", "gg", "+" and "Ctrl++" are all valid
+    fn parse(text: &str, context: KeyContext) -> Result<KeyBinding, String> {
+        let text = text.trim();
+        let (modifiers, key) = if text.len() > 1 && text.ends_with("++") {
+            (&text[..text.len() - 2], "+")
+        } else {
+            match text.rfind('+') {
+                Some(index) if index + 1 < text.len() => (&text[..index], &text[index + 1..]),
+                _ => ("", text)
+            }
+        };
+
+        // tags are case sensitive, so letters are too where tags are typed
+        let ignore_case = context == KeyContext::SidePanel || context == KeyContext::Menu;
+        let mut binding = KeyBinding {
+            prefix: None,
+            key: Key::Char(' '),
+            control: false,
+            shift: false,
+            alt: false,
+            ignore_case: ignore_case
+        };
+        let case = |x: char| if ignore_case { x.to_ascii_uppercase() } else { x };
+        for modifier in modifiers.split('+').filter(|x| !x.is_empty()) {
+            match modifier.to_ascii_lowercase().as_str() {
+                "ctrl" | "control" => binding.control = true,
+                "shift" => binding.shift = true,
+                "alt" => binding.alt = true,
+                _ => return Err(format!("Unknown modifier {} in {}", modifier, text))
+            }
+        }
+
+        let mut characters = key.chars();
+        binding.key = match (characters.next(), characters.next()) {
+            (Some(character), None) if binding.control || binding.alt => match char_key_code(character) {
+                Some(code) => Key::Code(code),
+                None => return Err(format!("{} can only be used without Ctrl or Alt", character))
+            },
+            (Some(_), None) if binding.shift => {
+                return Err(format!("Write {} as the character Shift types instead", text));
+            }
+            (Some(character), None) => Key::Char(case(character)),
+            _ => match NAMED_KEYS.iter().find(|x| x.0.eq_ignore_ascii_case(key)) {
+                Some((_, code)) => Key::Code(*code),
+                None => {
+                    let sequence: Vec::<char> = key.chars().collect();
+                    if sequence.len() != 2 || binding.control || binding.alt || binding.shift {
+                        return Err(format!("Unknown key {}", key));
+                    }
+                    binding.prefix = Some(case(sequence[0]));
+                    Key::Char(case(sequence[1]))
+                }
+            }
+        };
+
+        Ok(binding)
+    }
+
+    pub fn label(self: &Self) -> String {
+        let mut label = self.prefix.map(|x| x.to_string()).unwrap_or_default();
+        if self.control {
+            label.push_str("Ctrl+");
+        }
+        if self.alt {
+            label.push_str("Alt+");
+        }
+        if self.shift {
+            label.push_str("Shift+");
+        }
+
+        match self.key {
+            Key::Char(character) => label.push(character),
+            Key::Code(code) => match NAMED_KEYS.iter().find(|x| x.1 == code) {
+                Some((name, _)) => label.push_str(name),
+                None => label.push_str(&format!("{:?}", code))
+            }
+        }
+
+        label
+    }
+
+    fn matches_code(self: &Self, key_code: KeyCode, modifiers: ModifiersState) -> bool {
+        self.key == Key::Code(key_code)
+            && self.control == modifiers.control
+            && self.shift == modifiers.shift
+            && self.alt == modifiers.alt
+    }
+
+    fn same_char(self: &Self, x: char, y: char) -> bool {
+        if self.ignore_case { x.eq_ignore_ascii_case(&y) } else { x == y }
+    }
+
+    fn matches_char(self: &Self, prefix: Option<char>, character: char) -> bool {
+        let prefix_matches = match (self.prefix, prefix) {
+            (Some(x), Some(y)) => self.same_char(x, y),
+            (None, None) => true,
+            _ => false
+        };
+
+        match self.key {
+            Key::Char(_) if self.control || self.alt => false,
+            Key::Char(x) => prefix_matches && self.same_char(x, character),
+            Key::Code(_) => false
+        }
+    }
+
+    fn starts_with(self: &Self, character: char) -> bool {
+        match self.prefix {
+            Some(x) => self.same_char(x, character),
+            None => false
+        }
+    }
+
+    fn conflicts_with(self: &Self, other: &KeyBinding) -> bool {
+        if (self.control, self.shift, self.alt) != (other.control, other.shift, other.alt) {
+            return false;
+        }
+
+        match (self.key, other.key) {
+            // a key can't both do something and start a sequence
+            (Key::Char(x), _) if self.prefix.is_none() && other.starts_with(x) => true,
+            (_, Key::Char(y)) if other.prefix.is_none() && self.starts_with(y) => true,
+            (Key::Char(x), Key::Char(y)) => other.matches_char(self.prefix, x) || self.matches_char(other.prefix, y),
+            (x, y) => x == y
+        }
+    }
+}
+
+// a single key or a list of them
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum KeyList {
+    One(String),
+    Many(Vec::<String>),
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct KeymapFile {
+    #[serde(default)]
+    keys: HashMap<String, KeyList>,
+}
+
+#[derive(Debug)]
+pub struct Keymap {
+    bindings: Vec::<(KeyAction, KeyBinding)>,
+}
+
+pub fn keymap_path() -> Option<PathBuf> {
+    dirs::config_dir().map(|x| x.join(APP_DIRECTORY).join(KEYMAP_FILE))
+}
+
+impl Keymap {
+    pub fn new() -> Keymap {
+        let (keymap, _) = Keymap::build(&HashMap::new());
+        keymap
+    }
+
+    // Bad entries and conflicts don't stop the keymap from loading, they are
+    // returned so they can be reported and the rest of the keymap still applies.
+    pub fn load(path: &Path) -> Result<(Keymap, Vec::<String>), io::Error> {
+        let contents = fs::read_to_string(path)?;
+        let file: KeymapFile = toml::from_str(&contents).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        Ok(Keymap::build(&file.keys))
+    }
+
+    fn build(overrides: &HashMap<String, KeyList>) -> (Keymap, Vec::<String>) {
+        let mut problems = Vec::<String>::new();
+        let mut names: Vec::<&String> = overrides.keys().collect();
+        names.sort_unstable();
+        for name in names {
+            if KeyAction::from_name(name).is_none() {
+                problems.push(format!("Unknown action {} in keymap", name));
+            }
+        }
+
+        let mut keymap = Keymap { bindings: Vec::<(KeyAction, KeyBinding)>::new() };
+        for action in KeyAction::ALL.iter() {
+            let keys: Vec::<&str> = match overrides.get(action.name()) {
+                Some(KeyList::One(key)) => vec![key.as_str()],
+                Some(KeyList::Many(keys)) => keys.iter().map(|x| x.as_str()).collect(),
+                None => action.info().2.to_vec()
+            };
+
+            for key in keys {
+                let binding = match KeyBinding::parse(key, action.context()) {
+                    Ok(binding) => binding,
+                    Err(e) => {
+                        problems.push(format!("Error in keymap for {}: {}", action.name(), e));
+                        continue;
+                    }
+                };
+
+                match keymap.bindings.iter().find(|(x, y)| y.conflicts_with(&binding) && x.context().overlaps(&action.context())) {
+                    Some((existing, existing_binding)) => {
+                        problems.push(format!("Key conflict in keymap: {} clashes with {} bound to {}, left it off {}",
+                                              binding.label(), existing_binding.label(), existing.name(), action.name()));
+                    }
+                    None => keymap.bindings.push((*action, binding))
+                }
+            }
+        }
+
+        (keymap, problems)
+    }
+
+    fn is_active(action: &KeyAction, context: KeyContext) -> bool {
+        action.context() == context || (action.context() == KeyContext::Global && context != KeyContext::Menu)
+    }
+
+    pub fn action_for_key(self: &Self, context: KeyContext, key_code: KeyCode, modifiers: ModifiersState) -> Option<KeyAction> {
+        self.bindings
+            .iter()
+            .find(|(action, binding)| Keymap::is_active(action, context) && binding.matches_code(key_code, modifiers))
+            .map(|x| x.0)
+    }
+
+    pub fn action_for_char(self: &Self, context: KeyContext, character: char) -> Option<KeyAction> {
+        self.action_for_sequence(context, None, character)
+    }
+
+    pub fn action_for_sequence(self: &Self, context: KeyContext, prefix: Option<char>, character: char) -> Option<KeyAction> {
+        self.bindings
+            .iter()
+            .find(|(action, binding)| Keymap::is_active(action, context) && binding.matches_char(prefix, character))
+            .map(|x| x.0)
+    }
+
+    pub fn is_prefix(self: &Self, context: KeyContext, character: char) -> bool {
+        self.bindings
+            .iter()
+            .any(|(action, binding)| Keymap::is_active(action, context) && binding.starts_with(character))
+    }
+
+    // the action a tag key would be shadowed by, either directly or by starting a sequence
+    pub fn reserved_by(self: &Self, character: char) -> Option<KeyAction> {
+        self.action_for_char(KeyContext::Tagging, character).or_else(|| {
+            self.bindings
+                .iter()
+                .find(|(action, binding)| Keymap::is_active(action, KeyContext::Tagging) && binding.starts_with(character))
+                .map(|x| x.0)
+        })
+    }
+
+    pub fn action_for_event(self: &Self, context: KeyContext, event: &keyboard::Event) -> Option<KeyAction> {
+        match event {
+            keyboard::Event::KeyPressed { key_code, modifiers } => self.action_for_key(context, *key_code, *modifiers),
+            keyboard::Event::CharacterReceived(character) => self.action_for_char(context, *character),
+            _ => None
+        }
+    }
+
+    pub fn label(self: &Self, action: KeyAction) -> String {
+        let labels: Vec::<String> = self.bindings
+                                        .iter()
+                                        .filter(|x| x.0 == action)
+                                        .map(|x| x.1.label())
+                                        .collect();
+        if labels.is_empty() {
+            String::from("(unbound)")
+        } else {
+            labels.join("/")
+        }
+    }
+
+    pub fn help_lines(self: &Self, context: KeyContext) -> Vec::<String> {
+        KeyAction::ALL.iter()
+                      .filter(|x| x.context() == context)
+                      .map(|x| format!("{} - {}", self.label(*x), x.description()))
+                      .collect()
+    }
+}
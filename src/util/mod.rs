@@ -1,2 +1,18 @@
+pub mod animation;
+pub mod background;
+pub mod badge;
+pub mod cli;
+pub mod companions;
+pub mod duplicates;
+pub mod exif;
 pub mod file_io;
+pub mod fuzzy;
+pub mod histogram;
+pub mod icc;
 pub mod image;
+pub mod keymap;
+pub mod perceptual_hash;
+pub mod presets;
+pub mod raw;
+pub mod session;
+pub mod trash;
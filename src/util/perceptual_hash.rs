@@ -0,0 +1,123 @@
+use image::{ imageops::FilterType, GrayImage };
+
+use crate::util;
+
+// bits out of 64 two hashes may differ by and still count as the same picture
+const DISTANCE_THRESHOLD: u32 = 10;
+
+// Three 64 bit fingerprints of what an image looks like rather than its bytes,
+// so resized, recompressed or slightly different shots hash close together.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ImageHashes {
+    pub average: u64,
+    pub difference: u64,
+    pub dct: u64,
+}
+
+fn pixels(image: &GrayImage) -> Vec::<f64> {
+    image.pixels().map(|x| x[0] as f64).collect()
+}
+
+// aHash: each of 8x8 pixels brighter than the mean
+fn average_hash(image: &GrayImage) -> u64 {
+    let pixels = pixels(image);
+    let mean = pixels.iter().sum::<f64>() / pixels.len() as f64;
+    pixels.iter().fold(0, |hash, x| (hash << 1) | (*x > mean) as u64)
+}
+
+// dHash: each pixel of a 9x8 image brighter than its right neighbour
+fn difference_hash(image: &GrayImage) -> u64 {
+    let mut hash = 0;
+    for y in 0..8 {
+        for x in 0..8 {
+            let brighter = image.get_pixel(x, y)[0] > image.get_pixel(x + 1, y)[0];
+            hash = (hash << 1) | brighter as u64;
+        }
+    }
+    hash
+}
+
+// pHash: the lowest 8x8 frequencies of a 32x32 discrete cosine transform above their median
+fn dct_hash(image: &GrayImage) -> u64 {
+    let pixels = pixels(image);
+    let size = 32;
+    let mut coefficients = Vec::<f64>::new();
+    for v in 0..8 {
+        for u in 0..8 {
+            let mut sum = 0.0;
+            for y in 0..size {
+                for x in 0..size {
+                    sum += pixels[y * size + x]
+                         * ((2 * x + 1) as f64 * u as f64 * std::f64::consts::PI / (2 * size) as f64).cos()
+                         * ((2 * y + 1) as f64 * v as f64 * std::f64::consts::PI / (2 * size) as f64).cos();
+                }
+            }
+            coefficients.push(sum);
+        }
+    }
+
+    // the first coefficient is the overall brightness, which would skew the median
+    let mut sorted: Vec::<f64> = coefficients[1..].to_vec();
+    sorted.sort_by(|a, b| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal));
+    let median = sorted[sorted.len() / 2];
+    coefficients.iter().fold(0, |hash, x| (hash << 1) | (*x > median) as u64)
+}
+
+pub fn compute(path: &str) -> Result<ImageHashes, String> {
+    let image = util::image::decode(path)?;
+    // shrinking first keeps large photos quick, the hashes only look at 32 pixels across
+    let gray = image.thumbnail(128, 128).to_luma8();
+    let resize = |width, height| image::imageops::resize(&gray, width, height, FilterType::Triangle);
+
+    Ok(ImageHashes {
+        average: average_hash(&resize(8, 8)),
+        difference: difference_hash(&resize(9, 8)),
+        dct: dct_hash(&resize(32, 32))
+    })
+}
+
+impl ImageHashes {
+    // similar when at least two of the three hashes agree, since each one is fooled by different edits
+    pub fn is_similar(self: &Self, other: &ImageHashes) -> bool {
+        let close = [(self.average, other.average), (self.difference, other.difference), (self.dct, other.dct)]
+                        .iter()
+                        .filter(|(a, b)| (a ^ b).count_ones() <= DISTANCE_THRESHOLD)
+                        .count();
+        close >= 2
+    }
+}
+
+// Groups similar images, as indexes into hashes. Similarity chains, so a slow pan
+// across a scene ends up in one cluster even when its first and last shots differ.
+pub fn cluster(hashes: &[Option<ImageHashes>]) -> Vec::<Vec::<usize>> {
+    let mut parents: Vec::<usize> = (0..hashes.len()).collect();
+    fn root(parents: &mut Vec::<usize>, index: usize) -> usize {
+        let mut index = index;
+        while parents[index] != index {
+            parents[index] = parents[parents[index]];
+            index = parents[index];
+        }
+        index
+    }
+
+    for (i, a) in hashes.iter().enumerate() {
+        for (j, b) in hashes.iter().enumerate().skip(i + 1) {
+            if let (Some(a), Some(b)) = (a, b) {
+                if a.is_similar(b) {
+                    let (x, y) = (root(&mut parents, i), root(&mut parents, j));
+                    parents[y] = x;
+                }
+            }
+        }
+    }
+
+    let mut clusters = std::collections::BTreeMap::<usize, Vec::<usize>>::new();
+    for index in 0..hashes.len() {
+        let parent = root(&mut parents, index);
+        clusters.entry(parent).or_insert(Vec::<usize>::new()).push(index);
+    }
+
+    let mut clusters: Vec::<Vec::<usize>> = clusters.into_iter().map(|(_, x)| x).filter(|x| x.len() > 1).collect();
+    clusters.sort_unstable();
+    clusters
+}
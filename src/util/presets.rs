@@ -0,0 +1,112 @@
+use serde::{ Serialize, Deserialize };
+use std::{ fs, fmt, io, path::{ Path, PathBuf } };
+
+use crate::models::{ TagRegistry, TagColor, TagOrder };
+use crate::util::file_io::PROJECT_DIRECTORY;
+
+const APP_DIRECTORY: &str = "image_organizer";
+const PRESET_FILE: &str = "tags.toml";
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct TagPreset {
+    #[serde(default)]
+    pub tags: Vec::<PresetTag>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PresetTag {
+    pub name: String,
+    #[serde(default)]
+    pub key: Option<char>,
+    #[serde(default)]
+    pub color: Option<String>,
+}
+
+#[derive(Debug)]
+pub struct PresetConflict {
+    pub key: char,
+    pub bound_to: String,
+    pub rejected: String,
+    pub source: String,
+}
+
+impl fmt::Display for PresetConflict {
+    fn fmt(self: &Self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Key conflict in {}: {} is bound to {}, left {} without a key",
+               self.source, self.key, self.bound_to, self.rejected)
+    }
+}
+
+pub fn global_preset_path() -> Option<PathBuf> {
+    dirs::config_dir().map(|x| x.join(APP_DIRECTORY).join(PRESET_FILE))
+}
+
+pub fn project_preset_path() -> PathBuf {
+    Path::new(PROJECT_DIRECTORY).join(PRESET_FILE)
+}
+
+impl TagPreset {
+    pub fn load(path: &Path) -> Result<TagPreset, io::Error> {
+        let contents = fs::read_to_string(path)?;
+        toml::from_str(&contents).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+    }
+
+    pub fn save(self: &Self, path: &Path) -> Result<(), io::Error> {
+        if let Some(parent) = path.parent() {
+            if !parent.as_os_str().is_empty() {
+                fs::create_dir_all(parent)?;
+            }
+        }
+
+        let contents = toml::to_string(self).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        fs::write(path, contents)
+    }
+
+    pub fn from_registry(registry: &TagRegistry) -> TagPreset {
+        TagPreset {
+            // manual order so loading the tags back recreates that order as well
+            tags: registry.tree_by(TagOrder::Manual)
+                          .iter()
+                          .map(|(_, x)| PresetTag {
+                              name: registry.path(x.id),
+                              key: x.key,
+                              color: Some(x.color.to_hex())
+                          })
+                          .collect()
+        }
+    }
+
+    // Tags are matched by their full path, e.g. "animals/cats". A key that is already
+    // bound to a different tag keeps its current binding and the incoming tag is added
+    // without a key.
+    pub fn merge_into(self: &Self, registry: &mut TagRegistry, source: &str) -> Vec::<PresetConflict> {
+        let mut conflicts = Vec::<PresetConflict>::new();
+
+        for preset_tag in self.tags.iter() {
+            let key = match preset_tag.key {
+                Some(key) => match registry.by_key(key).map(|x| x.id) {
+                    Some(bound) if registry.find_path(&preset_tag.name) != Some(bound) => {
+                        conflicts.push(PresetConflict {
+                            key: key,
+                            bound_to: registry.path(bound),
+                            rejected: preset_tag.name.clone(),
+                            source: source.to_string()
+                        });
+                        None
+                    }
+                    _ => Some(key)
+                },
+                None => None
+            };
+
+            // keeps the binding an existing tag already has
+            let tag = registry.create_path(&preset_tag.name, key);
+            let color = preset_tag.color.as_ref().and_then(|x| TagColor::from_hex(x));
+            if let (Some(tag), Some(color)) = (tag, color) {
+                registry.set_color(tag, color);
+            }
+        }
+
+        conflicts
+    }
+}
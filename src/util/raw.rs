@@ -0,0 +1,223 @@
+use std::fs;
+
+use crate::util::{ companions, exif::Tiff };
+
+const TAG_IMAGE_WIDTH: u16 = 0x0100;
+const TAG_IMAGE_LENGTH: u16 = 0x0101;
+const TAG_BITS_PER_SAMPLE: u16 = 0x0102;
+const TAG_COMPRESSION: u16 = 0x0103;
+const TAG_PHOTOMETRIC: u16 = 0x0106;
+const TAG_STRIP_OFFSETS: u16 = 0x0111;
+const TAG_SAMPLES_PER_PIXEL: u16 = 0x0115;
+const TAG_STRIP_BYTE_COUNTS: u16 = 0x0117;
+const TAG_SUB_IFDS: u16 = 0x014A;
+const TAG_JPEG_OFFSET: u16 = 0x0201;
+const TAG_JPEG_LENGTH: u16 = 0x0202;
+const TAG_CFA_PATTERN: u16 = 0x828E;
+const TAG_BLACK_LEVEL: u16 = 0xC61A;
+const TAG_WHITE_LEVEL: u16 = 0xC61D;
+
+const COMPRESSION_NONE: u32 = 1;
+const COMPRESSION_JPEG: u32 = 6;
+const COMPRESSION_JPEG_DNG: u32 = 7;
+const PHOTOMETRIC_CFA: u32 = 32803;
+
+// bounds how far the IFD walk goes so a corrupt file can't send it round in circles
+const MAX_IFDS: usize = 32;
+
+pub enum Preview {
+    Jpeg(Vec::<u8>),
+    Rgb { width: u32, height: u32, pixels: Vec::<u8> },
+}
+
+pub fn is_raw(path: &str) -> bool {
+    companions::RAW_EXTENSIONS.contains(&companions::extension(path).as_str())
+}
+
+// where the JPEG starting at start ends, or None when it's cut short or isn't one
+// a regular decoder can show (lossless JPEG, which some RAWs keep their sensor data in)
+pub fn jpeg_end(data: &[u8], start: usize) -> Option<usize> {
+    if data.get(start..start + 2)? != [0xFF, 0xD8] {
+        return None;
+    }
+
+    let mut position = start + 2;
+    loop {
+        if *data.get(position)? != 0xFF {
+            return None;
+        }
+        let marker = *data.get(position + 1)?;
+        match marker {
+            0xFF => {
+                position = position + 1;
+                continue;
+            }
+            // lossless, hierarchical and arithmetic coded frames
+            0xC3 | 0xC5..=0xC7 | 0xC9..=0xCB | 0xCD..=0xCF => return None,
+            0xD9 => return Some(position + 2),
+            _ => ()
+        }
+
+        let length = u16::from_be_bytes([*data.get(position + 2)?, *data.get(position + 3)?]) as usize;
+        position = position + 2 + length;
+        if marker != 0xDA {
+            continue;
+        }
+
+        // entropy coded data runs until a marker that isn't a stuffed byte or a restart,
+        // progressive JPEGs have more segments and scans after it
+        loop {
+            if *data.get(position)? == 0xFF {
+                match *data.get(position + 1)? {
+                    0x00 | 0xD0..=0xD7 | 0xFF => (),
+                    _ => break
+                }
+            }
+            position = position + 1;
+        }
+    }
+}
+
+fn jpeg_at(data: &[u8], offset: usize, length: usize) -> Option<(usize, usize)> {
+    let end = jpeg_end(data, offset)?;
+    match end <= offset.checked_add(length)? {
+        true => Some((offset, end)),
+        false => None
+    }
+}
+
+struct RawImage {
+    ifd: usize,
+    width: u32,
+    height: u32,
+}
+
+fn walk_ifds(tiff: &Tiff, data: &[u8], jpegs: &mut Vec::<(usize, usize)>, raws: &mut Vec::<RawImage>) {
+    let mut pending: Vec::<usize> = tiff.first_ifd().into_iter().collect();
+    let mut seen = Vec::<usize>::new();
+    while let Some(ifd) = pending.pop() {
+        if seen.contains(&ifd) || seen.len() >= MAX_IFDS {
+            continue;
+        }
+        seen.push(ifd);
+        pending.extend(tiff.next_ifd(ifd));
+        if let Some(entry) = tiff.find(ifd, TAG_SUB_IFDS) {
+            pending.extend(tiff.numbers(&entry).iter().map(|x| *x as usize));
+        }
+
+        // thumbnails and previews stored the old JPEG way
+        if let (Some(offset), Some(length)) = (tiff.number(ifd, TAG_JPEG_OFFSET), tiff.number(ifd, TAG_JPEG_LENGTH)) {
+            jpegs.extend(jpeg_at(data, offset as usize, length as usize));
+        }
+
+        let compression = tiff.number(ifd, TAG_COMPRESSION).unwrap_or(COMPRESSION_NONE);
+        let offsets = tiff.find(ifd, TAG_STRIP_OFFSETS).map(|x| tiff.numbers(&x)).unwrap_or_default();
+        let counts = tiff.find(ifd, TAG_STRIP_BYTE_COUNTS).map(|x| tiff.numbers(&x)).unwrap_or_default();
+        match compression {
+            COMPRESSION_JPEG | COMPRESSION_JPEG_DNG if offsets.len() == 1 && counts.len() == 1 => {
+                jpegs.extend(jpeg_at(data, offsets[0] as usize, counts[0] as usize));
+            }
+            COMPRESSION_NONE if tiff.number(ifd, TAG_PHOTOMETRIC) == Some(PHOTOMETRIC_CFA) => {
+                if let (Some(width), Some(height)) = (tiff.number(ifd, TAG_IMAGE_WIDTH), tiff.number(ifd, TAG_IMAGE_LENGTH)) {
+                    raws.push(RawImage { ifd: ifd, width: width, height: height });
+                }
+            }
+            _ => ()
+        }
+    }
+}
+
+// Canon CR3 keeps its preview in a PRVW box inside an ISO media file
+fn cr3_preview(data: &[u8]) -> Option<(usize, usize)> {
+    let box_start = data.windows(4).position(|x| x == b"PRVW")?;
+    let start = box_start + data[box_start..].windows(2).take(64).position(|x| x == [0xFF, 0xD8])?;
+    Some((start, jpeg_end(data, start)?))
+}
+
+// Fuji RAF starts with its own header pointing at the preview
+fn raf_preview(data: &[u8]) -> Option<(usize, usize)> {
+    if data.get(0..15)? != b"FUJIFILMCCD-RAW" {
+        return None;
+    }
+    let number = |offset: usize| data.get(offset..offset + 4).map(|x| u32::from_be_bytes([x[0], x[1], x[2], x[3]]) as usize);
+    jpeg_at(data, number(84)?, number(88)?)
+}
+
+// A half size image from uncompressed sensor data, each 2x2 block of the colour
+// filter array becoming one pixel. Only used when a RAW has no preview to show.
+fn demosaic(tiff: &Tiff, data: &[u8], raw: &RawImage) -> Option<Preview> {
+    let bits = tiff.number(raw.ifd, TAG_BITS_PER_SAMPLE).unwrap_or(16);
+    let samples = tiff.number(raw.ifd, TAG_SAMPLES_PER_PIXEL).unwrap_or(1);
+    let offset = tiff.find(raw.ifd, TAG_STRIP_OFFSETS).and_then(|x| tiff.numbers(&x).first().cloned())? as usize;
+    if samples != 1 || (bits != 8 && bits != 16) {
+        return None;
+    }
+
+    let bytes_per_sample = (bits / 8) as usize;
+    let (width, height) = (raw.width as usize, raw.height as usize);
+    // the sizes come straight from the file, so a damaged one mustn't get past its end
+    let end = width.checked_mul(height)
+                   .and_then(|x| x.checked_mul(bytes_per_sample))
+                   .and_then(|x| x.checked_add(offset))?;
+    if width < 2 || height < 2 || end > data.len() {
+        return None;
+    }
+    let sample = |x: usize, y: usize| -> Option<f64> {
+        let position = offset + (y * width + x) * bytes_per_sample;
+        match bytes_per_sample {
+            1 => data.get(position).map(|x| *x as f64),
+            _ => tiff.u16_at(position).map(|x| x as f64)
+        }
+    };
+
+    // which of red (0), green (1) and blue (2) each position of the 2x2 pattern holds
+    let pattern: Vec::<u8> = tiff.find(raw.ifd, TAG_CFA_PATTERN)
+                                 .and_then(|x| tiff.bytes(&x))
+                                 .filter(|x| x.len() == 4)
+                                 .map(|x| x.to_vec())
+                                 .unwrap_or(vec![0, 1, 1, 2]);
+    let black = tiff.number(raw.ifd, TAG_BLACK_LEVEL).unwrap_or(0) as f64;
+    let white = tiff.number(raw.ifd, TAG_WHITE_LEVEL).unwrap_or((1 << bits) - 1) as f64;
+
+    let mut pixels = Vec::<u8>::with_capacity(width / 2 * height / 2 * 3);
+    for y in (0..height - height % 2).step_by(2) {
+        for x in (0..width - width % 2).step_by(2) {
+            let mut sums = [0.0_f64; 3];
+            let mut counts = [0.0_f64; 3];
+            for (i, (dx, dy)) in [(0, 0), (1, 0), (0, 1), (1, 1)].iter().enumerate() {
+                let channel = (pattern[i] as usize).min(2);
+                sums[channel] += sample(x + dx, y + dy)?;
+                counts[channel] += 1.0;
+            }
+            for channel in 0..3 {
+                let value = (sums[channel] / counts[channel].max(1.0) - black) / (white - black).max(1.0);
+                // a plain gamma curve, there's no colour profile to go by
+                pixels.push((value.max(0.0).min(1.0).powf(1.0 / 2.2) * 255.0) as u8);
+            }
+        }
+    }
+
+    Some(Preview::Rgb { width: (width / 2) as u32, height: (height / 2) as u32, pixels: pixels })
+}
+
+// the largest embedded JPEG, or a rough development of the sensor data when there's none
+pub fn preview(path: &str) -> Result<Preview, String> {
+    let data = fs::read(path).map_err(|e| e.to_string())?;
+
+    let mut jpegs = Vec::<(usize, usize)>::new();
+    let mut raws = Vec::<RawImage>::new();
+    match Tiff::new(&data) {
+        Some(tiff) => walk_ifds(&tiff, &data, &mut jpegs, &mut raws),
+        None => jpegs.extend(cr3_preview(&data).or(raf_preview(&data)))
+    }
+
+    if let Some((start, end)) = jpegs.into_iter().max_by_key(|(start, end)| end - start) {
+        return Ok(Preview::Jpeg(data[start..end].to_vec()));
+    }
+
+    let tiff = Tiff::new(&data).ok_or(String::from("no preview found"))?;
+    raws.iter()
+        .max_by_key(|x| x.width as u64 * x.height as u64)
+        .and_then(|raw| demosaic(&tiff, &data, raw))
+        .ok_or(String::from("no preview found and the sensor data is compressed or damaged"))
+}
@@ -0,0 +1,118 @@
+use serde::{ Serialize, Deserialize };
+use std::{ fs, io, collections::HashMap, path::{ Path, PathBuf } };
+
+use crate::models::{ History, ImageInfo, SavedAction, TagOrder, TagRegistry };
+use crate::util::{ file_io::PROJECT_DIRECTORY, presets::{ TagPreset, PresetTag } };
+
+const SESSION_FILE: &str = "session.toml";
+
+// plain values have to come before the lists of tables for toml to serialize this
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Session {
+    pub order: TagOrder,
+    #[serde(default)]
+    pub tags: Vec::<PresetTag>,
+    #[serde(default)]
+    pub images: Vec::<SessionImage>,
+    #[serde(default)]
+    pub history: Vec::<SavedAction>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SessionImage {
+    pub path: String,
+    #[serde(default)]
+    pub tags: Vec::<String>,
+    #[serde(default)]
+    pub visited: bool,
+    #[serde(default)]
+    pub marked_for_deletion: bool,
+}
+
+pub fn session_path() -> PathBuf {
+    Path::new(PROJECT_DIRECTORY).join(SESSION_FILE)
+}
+
+impl Session {
+    pub fn capture(registry: &TagRegistry, image_infos: &[ImageInfo], history: &History) -> Session {
+        Session {
+            order: registry.order,
+            tags: TagPreset::from_registry(registry).tags,
+            images: image_infos.iter()
+                               .filter(|x| !x.tags.is_empty() || x.visited || x.marked_for_deletion)
+                               .map(|x| {
+                                   let mut tags: Vec::<String> = x.tags.iter().map(|tag| registry.path(*tag)).collect();
+                                   tags.sort_unstable();
+                                   SessionImage {
+                                       path: x.path.clone(),
+                                       tags: tags,
+                                       visited: x.visited,
+                                       marked_for_deletion: x.marked_for_deletion
+                                   }
+                               })
+                               .collect(),
+            history: history.to_saved(registry, image_infos)
+        }
+    }
+
+    // the work quitting would lose: the tags, their order and which images have them or are
+    // marked for deletion, but not which images were looked at or the undo history
+    pub fn snapshot(self: &Self) -> String {
+        let images: Vec::<String> = self.images
+                                        .iter()
+                                        .filter(|x| !x.tags.is_empty() || x.marked_for_deletion)
+                                        .map(|x| format!("{} {:?} {}", x.path, x.tags, x.marked_for_deletion))
+                                        .collect();
+
+        format!("{:?} {:?} {:?}", self.order, self.tags, images)
+    }
+
+    pub fn load(path: &Path) -> Result<Session, io::Error> {
+        let contents = fs::read_to_string(path)?;
+        toml::from_str(&contents).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+    }
+
+    pub fn save(self: &Self, path: &Path) -> Result<(), io::Error> {
+        if let Some(parent) = path.parent() {
+            if !parent.as_os_str().is_empty() {
+                fs::create_dir_all(parent)?;
+            }
+        }
+
+        let contents = toml::to_string(self).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        fs::write(path, contents)
+    }
+
+    // Replaces the registry with the saved tags and re-applies them to the queue.
+    // Returns the paths of saved images that are no longer in the folder.
+    pub fn restore(self: &Self, registry: &mut TagRegistry, image_infos: &mut [ImageInfo]) -> Vec::<String> {
+        *registry = TagRegistry::new();
+        TagPreset { tags: self.tags.clone() }.merge_into(registry, "session");
+        registry.order = self.order;
+
+        let indexes: HashMap<String, usize> = image_infos.iter()
+                                                         .enumerate()
+                                                         .map(|(i, x)| (x.path.clone(), i))
+                                                         .collect();
+        for image_info in image_infos.iter_mut() {
+            image_info.tags.clear();
+            image_info.visited = false;
+            image_info.marked_for_deletion = false;
+        }
+
+        let mut missing = Vec::<String>::new();
+        for saved in self.images.iter() {
+            match indexes.get(&saved.path) {
+                Some(index) => {
+                    let image_info = &mut image_infos[*index];
+                    image_info.visited = saved.visited;
+                    image_info.marked_for_deletion = saved.marked_for_deletion;
+                    image_info.tags = saved.tags.iter().filter_map(|x| registry.find_path(x)).collect();
+                }
+                None => missing.push(saved.path.clone())
+            }
+        }
+
+        missing
+    }
+}
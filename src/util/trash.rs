@@ -0,0 +1,107 @@
+use std::{ env, fs, io, path::{ Path, PathBuf }, time::{ SystemTime, UNIX_EPOCH } };
+
+// https://specifications.freedesktop.org/trash-spec/trashspec-latest.html
+fn trash_directory() -> Result<PathBuf, io::Error> {
+    let data_home = match env::var_os("XDG_DATA_HOME") {
+        Some(x) if !x.is_empty() => PathBuf::from(x),
+        _ => match env::var_os("HOME") {
+            Some(home) => PathBuf::from(home).join(".local/share"),
+            None => return Err(io::Error::new(io::ErrorKind::NotFound, "HOME is not set"))
+        }
+    };
+
+    Ok(data_home.join("Trash"))
+}
+
+// picks "name", "name.2", "name.3"... so existing files are never overwritten
+fn unused_name<F>(file_name: &str, is_taken: F) -> String where F: Fn (&str) -> bool {
+    let mut name = file_name.to_string();
+    let mut counter = 1;
+    while is_taken(&name) {
+        counter = counter + 1;
+        name = format!("{}.{}", file_name, counter);
+    }
+
+    name
+}
+
+fn file_name(path: &Path) -> Result<String, io::Error> {
+    path.file_name()
+        .map(|x| x.to_string_lossy().to_string())
+        .ok_or(io::Error::new(io::ErrorKind::InvalidInput, "Path has no file name"))
+}
+
+fn percent_encode(path: &Path) -> String {
+    path.to_string_lossy()
+        .bytes()
+        .map(|x| match x {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'/' | b'-' | b'_' | b'.' | b'~' => (x as char).to_string(),
+            _ => format!("%{:02X}", x)
+        })
+        .collect()
+}
+
+// the spec asks for local time, without a timezone database UTC is the best we can do
+fn deletion_date() -> String {
+    let seconds = SystemTime::now().duration_since(UNIX_EPOCH).map(|x| x.as_secs()).unwrap_or(0);
+    let days = (seconds / 86400) as i64;
+    let time = seconds % 86400;
+
+    // days to civil date, from http://howardhinnant.github.io/date_algorithms.html
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let day_of_era = z - era * 146097;
+    let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let mp = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+
+    format!("{:04}-{:02}-{:02}T{:02}:{:02}:{:02}", year, month, day, time / 3600, (time % 3600) / 60, time % 60)
+}
+
+// rename only works within a filesystem, fall back to copying
+fn move_file(source: &Path, destination: &Path) -> Result<(), io::Error> {
+    if fs::rename(source, destination).is_err() {
+        fs::copy(source, destination)?;
+        fs::remove_file(source)?;
+    }
+
+    Ok(())
+}
+
+pub fn move_to_trash(source: &Path) -> Result<PathBuf, io::Error> {
+    // not canonicalized, a symlink should be trashed rather than what it points to
+    let source = env::current_dir()?.join(source);
+    let trash = trash_directory()?;
+    let files = trash.join("files");
+    let info = trash.join("info");
+    fs::create_dir_all(&files)?;
+    fs::create_dir_all(&info)?;
+
+    let name = unused_name(&file_name(&source)?, |x| {
+        files.join(x).exists() || info.join(format!("{}.trashinfo", x)).exists()
+    });
+
+    let info_file = info.join(format!("{}.trashinfo", name));
+    fs::write(&info_file, format!("[Trash Info]\nPath={}\nDeletionDate={}\n",
+                                  percent_encode(&source), deletion_date()))?;
+
+    let destination = files.join(&name);
+    if let Err(e) = move_file(&source, &destination) {
+        let _ = fs::remove_file(&info_file);
+        return Err(e);
+    }
+
+    Ok(destination)
+}
+
+pub fn move_to_quarantine(source: &Path, quarantine: &Path) -> Result<PathBuf, io::Error> {
+    fs::create_dir_all(quarantine)?;
+    let name = unused_name(&file_name(source)?, |x| quarantine.join(x).exists());
+    let destination = quarantine.join(name);
+    move_file(source, &destination)?;
+
+    Ok(destination)
+}
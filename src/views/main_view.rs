@@ -1,11 +1,12 @@
-use iced::{ scrollable, Length, pane_grid, PaneGrid };
+use iced::{ scrollable, Length, pane_grid, PaneGrid, Row, Text, ProgressBar, Element, Align };
 use iced_native::{ text_input, keyboard };
-use std::{ fs, cell::RefCell };
+use std::collections::HashSet;
 
 use crate::app::App;
 use crate::states::*;
 use crate::models::*;
-use crate::lib_ext::*;
+use crate::style;
+use crate::util::keymap::{ KeyAction, KeyContext };
 
 #[derive(Debug)]
 pub struct MainView {
@@ -29,7 +30,10 @@ impl MainView {
                 AppView::SidePanel(state) => state.view(&mut content.scroll),
                 AppView::ImageQueue(state) => state.view(&mut content.scroll),
                 AppView::ImageDisplay(state) => state.view(),
-                AppView::TagInput(state) => state.view(&mut content.scroll, &mut content.text_input_state)
+                AppView::TagInput(state) => state.view(&mut content.scroll, &mut content.text_input_state),
+                AppView::TagPicker(state) => state.view(&mut content.scroll, &mut content.text_input_state),
+                AppView::Help(state) => state.view(&mut content.scroll),
+                AppView::CommandPalette(state) => state.view(&mut content.scroll, &mut content.text_input_state)
             }
         })
         .width(Length::Fill)
@@ -37,114 +41,206 @@ impl MainView {
         .on_resize(10, Message::Resized)
     }
 
+    pub fn progress_view<'a>(statistics: &TagStatistics, status: String) -> Element<'a, Message> {
+        let total = statistics.total();
+        let mut summary = format!("Tagged {} / {}    Visited {} / {}",
+                                  statistics.tagged, total, statistics.visited, total);
+        if let Some(remaining) = statistics.remaining {
+            summary.push_str(&format!("    ETA {}", format_duration(remaining)));
+        }
+
+        Row::<'_, Message>::new()
+            .spacing(10)
+            .padding(5)
+            .align_items(Align::Center)
+            .push(ProgressBar::new(0.0..=total.max(1) as f32, statistics.tagged as f32)
+                    .height(Length::Units(12))
+                    .style(style::Progress { }))
+            .push(Text::new(summary).size(16))
+            .push(Text::new(status).size(16))
+            .into()
+    }
+
     pub fn handle_keyboard(app: &mut App, event: keyboard::Event) {
-        match event {
-            keyboard::Event::KeyPressed { key_code, .. } => {
-                // allow pulling up menu regardless of keyboard state
-                if let keyboard::KeyCode::Escape = key_code {
-                    app.app_state = AppState::Menu
-                }
+        let context = match app.keyboard_state {
+            KeyboardState::Tagging => KeyContext::Tagging,
+            KeyboardState::SidePanel => KeyContext::SidePanel,
+            KeyboardState::Rebinding(tag) => return MainView::handle_rebinding(app, tag, event),
+            KeyboardState::None => return MainView::handle_typing(app, event)
+        };
 
-                if let KeyboardState::Tagging = app.keyboard_state {
-                    match key_code {
-                        keyboard::KeyCode::Left => {
-                            let state = app.get_mut_state(app.image_queue).image_queue_mut();
-                            match state.image_infos.prev(state.selected_image_index, |_| true) {
-                                Some(x) => state.selected_image_index = x,
-                                _ => ()
-                            }
-                        },
-                        keyboard::KeyCode::Right => {
-                            let state = app.get_mut_state(app.image_queue).image_queue_mut();
-                            match state.image_infos.next(state.selected_image_index, |_| true) {
-                                Some(x) => state.selected_image_index = x,
-                                _ => ()
-                            }
-                        },
-                        keyboard::KeyCode::Delete => {
-                            let state = app.get_state(app.image_queue).image_queue();
-                            if state.selected_image_index < state.image_infos.len() {
-                                if let Some(x) = state.image_infos.get(state.selected_image_index) {
-                                    if let Err(e) = fs::remove_file(&x.path) {
-                                        app.log(format!("Error deleting {} : {}", x.path, e));
-                                    } else {
-                                        app.log(format!("Deleted {} successfully", x.path));
-                                        app.get_mut_state(app.image_queue)
-                                           .image_queue_mut()
-                                           .delete_current();
-                                    }
-                                } else {
-                                    app.log(String::from("Error getting path of current file"));
-                                }
-                            }
-                        },
-                        _ => ()
-                    }
-                }
+        if let (KeyContext::Tagging, keyboard::Event::CharacterReceived(character)) = (context, event) {
+            if MainView::handle_pending_keys(app, character) {
+                return;
             }
-            keyboard::Event::CharacterReceived(character) => {
-                match app.app_state {
-                    AppState::Tagging => {
-                        match app.keyboard_state {
-                            KeyboardState::Tagging => { 
-                                if character.is_alphabetic() {
-                                    if !app.does_tag_exist(&character.to_string()) {
-                                        app.keyboard_state = KeyboardState::None;
-                                        let tag_input_content = MainView::new(AppView::TagInput(TagInputState { 
-                                            tag_input_value: RefCell::new(String::from("")),
-                                            tag: character
-                                        }));
-
-                                        let (pane, split) = app.pane_state
-                                                               .split(pane_grid::Axis::Horizontal, 
-                                                                    &app.image_display, tag_input_content)
-                                                               .expect("Pane couldn't split");
-                                        app.tag_input = Some(pane);
-                                        app.pane_state.resize(&split, 0.9);
-                                    }
-
-                                    app.toggle_tag_on_current_image(&character);
-                                } else {
-                                    let state = app.get_mut_state(app.image_queue).image_queue_mut();
-                                    match character {
-                                        '[' => {
-                                            if let Some(x) = state.image_infos
-                                                                  .prev(state.selected_image_index,
-                                                                          |x| x.tags.is_empty()) {
-                                                state.selected_image_index = x;
-                                            }
-                                        },
-                                        ']' => {
-                                            if let Some(x) = state.image_infos
-                                                                  .next(state.selected_image_index,
-                                                                          |x| x.tags.is_empty()) {
-                                                state.selected_image_index = x;
-                                            }
-                                        },
-                                        '{' => {
-                                            if let Some(x) = state.image_infos
-                                                                  .prev(state.selected_image_index,
-                                                                          |x| !x.tags.is_empty()) {
-                                                state.selected_image_index = x;
-                                            }
-                                        },
-                                        '}' => {
-                                            if let Some(x) = state.image_infos
-                                                                  .next(state.selected_image_index,
-                                                                          |x| !x.tags.is_empty()) {
-                                                state.selected_image_index = x;
-                                            }
-                                        },
-                                        _ => ()
-                                    }
-                                }
-                            }
-                            KeyboardState::None => ()
+        }
+
+        match app.keymap.action_for_event(context, &event) {
+            Some(action) => {
+                let count = app.pending_keys.count.take();
+                app.pending_keys.clear();
+                MainView::perform(app, action, count);
+            }
+            None => {
+                // letters that aren't bound to an action are tags
+                if let (KeyContext::Tagging, keyboard::Event::CharacterReceived(character)) = (context, event) {
+                    app.pending_keys.clear();
+                    if character.is_alphabetic() {
+                        match app.tag_for_key(character) {
+                            Some(tag) => app.toggle_tag_on_current_image(tag),
+                            None => app.open_tag_input(TagInputTarget::Create(character))
                         }
                     }
+                }
+            }
+        }
+    }
+
+    // Counts, sequences like gg and tag jumps are built up over several keys.
+    // Returns true when the key was taken up by one of them.
+    fn handle_pending_keys(app: &mut App, character: char) -> bool {
+        if app.pending_keys.group_tag {
+            app.pending_keys.clear();
+            if let Some(tag) = app.tag_for_key(character) {
+                app.toggle_tag_on_cluster(tag);
+            }
+            return true;
+        }
+
+        if let Some(forward) = app.pending_keys.tag_jump {
+            let count = app.pending_keys.count.unwrap_or(1);
+            app.pending_keys.clear();
+            if let Some(tag) = app.tag_for_key(character) {
+                app.jump_to_tag(tag, count, forward);
+            }
+            return true;
+        }
+
+        if let Some(prefix) = app.pending_keys.prefix {
+            let count = app.pending_keys.count;
+            app.pending_keys.clear();
+            if let Some(action) = app.keymap.action_for_sequence(KeyContext::Tagging, Some(prefix), character) {
+                MainView::perform(app, action, count);
+            }
+            return true;
+        }
+
+        // digits only count when they aren't bound to something, and 0 can't start a count
+        if let Some(digit) = character.to_digit(10) {
+            let is_bound = app.keymap.action_for_char(KeyContext::Tagging, character).is_some();
+            if !is_bound && (digit > 0 || app.pending_keys.count.is_some()) {
+                app.pending_keys.push_digit(digit);
+                return true;
+            }
+        }
+
+        if app.keymap.is_prefix(KeyContext::Tagging, character) {
+            app.pending_keys.prefix = Some(character);
+            return true;
+        }
+
+        false
+    }
+
+    pub fn perform(app: &mut App, action: KeyAction, count: Option<usize>) {
+        let selected_tag = app.selected_side_panel_tag();
+        let repeat = count.unwrap_or(1);
+        match action {
+            KeyAction::OpenMenu => app.app_state = AppState::Menu,
+            KeyAction::Undo => app.undo(),
+            KeyAction::Redo => app.redo(),
+            KeyAction::Help => app.toggle_help(),
+            KeyAction::CommandPalette => app.open_command_palette(),
+            KeyAction::NextImage | KeyAction::PrevImage | KeyAction::NextUntagged |
+            KeyAction::PrevUntagged | KeyAction::NextTagged | KeyAction::PrevTagged |
+            KeyAction::FirstImage | KeyAction::LastImage => {
+                let state = app.get_mut_state(app.image_queue).image_queue_mut();
+                match (action, count) {
+                    (KeyAction::NextImage, _) => { state.select_next_nth(repeat, |_| true); },
+                    (KeyAction::PrevImage, _) => { state.select_prev_nth(repeat, |_| true); },
+                    (KeyAction::NextUntagged, _) => { state.select_next_nth(repeat, |x| x.tags.is_empty()); },
+                    (KeyAction::PrevUntagged, _) => { state.select_prev_nth(repeat, |x| x.tags.is_empty()); },
+                    (KeyAction::NextTagged, _) => { state.select_next_nth(repeat, |x| !x.tags.is_empty()); },
+                    (KeyAction::PrevTagged, _) => { state.select_prev_nth(repeat, |x| !x.tags.is_empty()); },
+                    (KeyAction::FirstImage, Some(number)) | (KeyAction::LastImage, Some(number)) => {
+                        state.select_number(number);
+                    },
+                    (KeyAction::FirstImage, None) => { state.select_first(); },
+                    (KeyAction::LastImage, None) => { state.select_last(); },
+                    _ => ()
+                }
+            }
+            KeyAction::NextWithTag | KeyAction::PrevWithTag => {
+                app.pending_keys.count = count;
+                app.pending_keys.tag_jump = Some(action == KeyAction::NextWithTag);
+            }
+            KeyAction::FindSimilar => app.find_similar_to_current(),
+            KeyAction::NextSimilar | KeyAction::PrevSimilar => {
+                let state = app.get_state(app.image_queue).image_queue();
+                let cluster: HashSet<String> = state.cluster_of(state.selected_image_index)
+                                                    .iter()
+                                                    .map(|x| state.image_infos[*x].path.clone())
+                                                    .collect();
+                let state = app.get_mut_state(app.image_queue).image_queue_mut();
+                match action {
+                    KeyAction::NextSimilar => state.select_next_nth(repeat, |x| cluster.contains(&x.path)),
+                    _ => state.select_prev_nth(repeat, |x| cluster.contains(&x.path))
+                };
+            }
+            KeyAction::TagCluster => app.pending_keys.group_tag = true,
+            KeyAction::ToggleStack => app.toggle_stack(),
+            KeyAction::PickStack => app.set_stack_pick(),
+            KeyAction::NextPage => app.change_page(1),
+            KeyAction::PrevPage => app.change_page(-1),
+            KeyAction::ToggleAnimation => app.toggle_animation(),
+            KeyAction::NextFrame => app.step_frame(1),
+            KeyAction::PrevFrame => app.step_frame(-1),
+            KeyAction::ToggleClipping => app.toggle_clipping(),
+            KeyAction::MarkForDeletion => app.mark_current_for_deletion(),
+            KeyAction::RestoreDeleted => app.restore_last_deleted(),
+            KeyAction::KeepCopy => app.keep_current_copy(),
+            KeyAction::SearchTags => app.open_tag_picker(),
+            KeyAction::FocusSidePanel => app.focus_side_panel(true),
+            KeyAction::Filter => app.open_tag_input(TagInputTarget::Filter),
+            KeyAction::ClearFilter => app.apply_filter(""),
+            KeyAction::LeaveSidePanel => app.focus_side_panel(false),
+            KeyAction::SelectTagUp => app.move_side_panel_selection(-1),
+            KeyAction::SelectTagDown => app.move_side_panel_selection(1),
+            _ => if let Some(tag) = selected_tag {
+                match action {
+                    KeyAction::MoveTagUp => app.move_tag(tag, -1),
+                    KeyAction::MoveTagDown => app.move_tag(tag, 1),
+                    KeyAction::RenameTag => app.open_tag_input(TagInputTarget::Rename(tag)),
+                    KeyAction::RebindTag => app.start_rebinding_tag(tag),
+                    KeyAction::CycleColor => app.tags.borrow_mut().cycle_color(tag),
+                    KeyAction::CycleOrder => app.cycle_tag_order(),
+                    KeyAction::DeleteTag => app.remove_tag(tag),
                     _ => ()
                 }
             }
+        }
+    }
+
+    // allow pulling up menu while typing in a pane too
+    fn handle_typing(app: &mut App, event: keyboard::Event) {
+        if let keyboard::Event::KeyPressed { key_code, modifiers } = event {
+            if let Some(KeyAction::OpenMenu) = app.keymap.action_for_key(KeyContext::Global, key_code, modifiers) {
+                app.app_state = AppState::Menu;
+            }
+        }
+    }
+
+    fn handle_rebinding(app: &mut App, tag: TagId, event: keyboard::Event) {
+        match event {
+            keyboard::Event::KeyPressed { key_code, .. } => match key_code {
+                keyboard::KeyCode::Delete | keyboard::KeyCode::Backspace => app.rebind_tag(tag, None),
+                _ => MainView::handle_typing(app, event)
+            },
+            keyboard::Event::CharacterReceived(character) => {
+                if character.is_alphabetic() {
+                    app.rebind_tag(tag, Some(character));
+                }
+            }
             _ => ()
         }
     }
@@ -2,46 +2,89 @@ use iced::{ Text, Column, Row, Container, Radio };
 use iced_native::{ keyboard };
 use crate::app::App;
 use crate::states::{ Message, OrganizeMode, AppState };
+use crate::util::keymap::{ KeyAction, KeyContext };
 
 pub struct MenuView { }
 
 impl MenuView {
     pub fn handle_keyboard(app: &mut App, event: keyboard::Event) {
-        match event {
-            keyboard::Event::KeyPressed { key_code, .. } => {
-                match key_code {
-                    keyboard::KeyCode::Escape => {
-                        app.app_state = AppState::Tagging 
-                    }
-                    keyboard::KeyCode::Q => {
-                        // TODO: exit more gracefully
-                        assert!(1 == 0); // ¯\_(ツ)_/¯
-                    }
-                    keyboard::KeyCode::O => {
-                        app.organize_mode.next();
-                    }
-                    keyboard::KeyCode::R => {
-                        match app.run_organize_process() {
-                            Err(e) => panic!("Error running process: {}", e),
-                            _ => ()
-                        }
-                    }
-                    keyboard::KeyCode::C => {
-                        app.console_messages.borrow_mut().clear();
-                    }
+        if app.quit_prompt {
+            return MenuView::handle_quit_prompt(app, event);
+        }
+
+        if let Some(action) = app.keymap.action_for_event(KeyContext::Menu, &event) {
+            MenuView::perform(app, action);
+        }
+    }
+
+    pub fn perform(app: &mut App, action: KeyAction) {
+        match action {
+            KeyAction::CloseMenu => {
+                app.app_state = AppState::Tagging 
+            }
+            KeyAction::Quit => app.request_quit(),
+            KeyAction::CycleOrganizeMode => {
+                app.organize_mode.next();
+            }
+            KeyAction::RunOrganize => {
+                match app.run_organize_process() {
+                    Err(e) => panic!("Error running process: {}", e),
                     _ => ()
                 }
+            }
+            KeyAction::ToggleOrganizeFiltered => {
+                app.organize_filtered = !app.organize_filtered;
+            }
+            KeyAction::ToggleOrganizeCorrupt => {
+                app.organize_corrupt = !app.organize_corrupt;
+            }
+            KeyAction::FindDuplicates => app.find_duplicates(),
+            KeyAction::ClusterSimilar => app.cluster_similar_images(),
+            KeyAction::StackBursts => app.stack_bursts(),
+            KeyAction::ClearConsole => {
+                app.console_messages.borrow_mut().clear();
+            }
+            KeyAction::SaveSession => { app.save_session(); },
+            KeyAction::LoadSession => app.load_session(),
+            KeyAction::MergeGlobalPreset => app.merge_global_preset(),
+            KeyAction::MergeProjectPreset => app.merge_project_preset(),
+            KeyAction::WriteProjectPreset => app.save_project_preset(),
+            KeyAction::ExportGlobalPreset => app.save_global_preset(),
+            _ => ()
+        }
+    }
+
+    fn handle_quit_prompt(app: &mut App, event: keyboard::Event) {
+        match event {
+            keyboard::Event::CharacterReceived(character) => match character.to_ascii_uppercase() {
+                'Y' => app.quit(true),
+                'N' => app.quit(false),
+                'C' => app.quit_prompt = false,
+                _ => ()
             },
+            keyboard::Event::KeyPressed { key_code: keyboard::KeyCode::Escape, .. } => app.quit_prompt = false,
             _ => ()
         }
     }
 
+    fn line(app: &App, action: KeyAction) -> Text {
+        Text::new(format!("{} - {}", app.keymap.label(action), action.description()))
+    }
+
     pub fn view(app: &App) -> Column<Message> {
+        if app.quit_prompt {
+            return Column::<'_, Message>::new()
+                       .push(Text::new("There are unsaved tag changes. Save the session before quitting?"))
+                       .push(Text::new("Y - Save and Quit"))
+                       .push(Text::new("N - Quit Without Saving"))
+                       .push(Text::new("C/Escape - Cancel"));
+        }
+
         let column = Column::<'_, Message>::new()
                         .push(Row::<'_, Message>::new()
-                            .push(Container::new(Text::new("Q - Quit"))))
+                            .push(Container::new(MenuView::line(app, KeyAction::Quit))))
                         .push(Row::<'_, Message>::new()
-                            .push(Container::new(Text::new("O - Organize Mode")))
+                            .push(Container::new(MenuView::line(app, KeyAction::CycleOrganizeMode)))
                             .push(Radio::new(
                                     OrganizeMode::Copy, 
                                     "Copy", 
@@ -57,13 +100,31 @@ impl MenuView {
                                     "Link", 
                                     Some(app.organize_mode), 
                                     Message::SelectedOrganizeMode))
-                            )
-                        .push(Row::<'_, Message>::new()
-                            .push(Container::new(Text::new("R - Run Organize Process"))))
-                        .push(Row::<'_, Message>::new()
-                            .push(Container::new(Text::new("C - Clear Console"))))
-                        .push(Row::<'_, Message>::new()
-                            .push(Container::new(Text::new("Escape - Close Menu"))));
+                            );
+
+        let on_off = |on| match on {
+            true => "On",
+            false => "Off"
+        };
+        let column = column.push(Row::<'_, Message>::new()
+                                    .spacing(10)
+                                    .push(Container::new(MenuView::line(app, KeyAction::ToggleOrganizeFiltered)))
+                                    .push(Text::new(on_off(app.organize_filtered))))
+                           .push(Row::<'_, Message>::new()
+                                    .spacing(10)
+                                    .push(Container::new(MenuView::line(app, KeyAction::ToggleOrganizeCorrupt)))
+                                    .push(Text::new(on_off(app.organize_corrupt))));
+
+        let actions = [KeyAction::RunOrganize, KeyAction::FindDuplicates, KeyAction::ClusterSimilar,
+                       KeyAction::StackBursts, KeyAction::SaveSession, KeyAction::LoadSession,
+                       KeyAction::MergeGlobalPreset, KeyAction::MergeProjectPreset,
+                       KeyAction::WriteProjectPreset, KeyAction::ExportGlobalPreset,
+                       KeyAction::ClearConsole, KeyAction::CloseMenu];
+        let column = actions.iter()
+                            .fold(column, |acc, action| {
+                                acc.push(Row::<'_, Message>::new()
+                                            .push(Container::new(MenuView::line(app, *action))))
+                            });
 
         app.console_messages.borrow()
                             .iter()